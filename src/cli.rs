@@ -0,0 +1,197 @@
+use crate::config::{builtin_materials, Config, Param, CONFIG_PARAMS, LAYER_PARAMS, OBJECT_PARAMS};
+use std::fmt::Write;
+
+/// What the program was asked to do
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Runs the simulations and writes the result
+    Simulate,
+    /// Copies the backup over the save file
+    Restore,
+    /// Lists the levels in the save file
+    List,
+    /// Shows the selected level's settings and bodies
+    Inspect,
+    /// Removes everything a previous run generated
+    Clean,
+    /// Writes the selected level to a .gmd or level string file
+    Export,
+    Help
+}
+impl Command {
+    pub fn from(name: &str) -> Option<Command> {
+        match name {
+            "simulate" => Some(Command::Simulate),
+            "restore" => Some(Command::Restore),
+            "list" => Some(Command::List),
+            "inspect" => Some(Command::Inspect),
+            "clean" => Some(Command::Clean),
+            "export" => Some(Command::Export),
+            "help" => Some(Command::Help),
+            _ => None
+        }
+    }
+}
+/// The parsed command line
+#[derive(Clone, Debug, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub config_path: Option<String>,
+    /// The file `export` writes to, overriding `output_path`
+    pub export_path: Option<String>,
+    /// Overrides `level_name`
+    pub level: Option<String>,
+    /// Only runs the simulations on these layers
+    pub layers: Vec<u16>,
+    /// Overrides `sim_time` for every simulation
+    pub sim_time: Option<f32>,
+    /// Overrides `path`
+    pub save: Option<String>,
+    pub dry_run: bool
+}
+impl Args {
+    /// Parses the arguments after the program name
+    ///
+    /// `gdphysics config.json [restore|list|clean]` is also accepted, as older versions used it
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        let mut parsed = Args {
+            command: Command::Simulate,
+            config_path: None,
+            export_path: None,
+            level: None,
+            layers: Vec::new(),
+            sim_time: None,
+            save: None,
+            dry_run: false
+        };
+        let mut positional = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| args.next().ok_or(format!("{flag} needs a value"));
+            match arg.as_str() {
+                "-h" | "--help" => parsed.command = Command::Help,
+                "--dry-run" => parsed.dry_run = true,
+                "--level" => parsed.level = Some(value("--level")?),
+                "--save" => parsed.save = Some(value("--save")?),
+                "--layer" => {
+                    let layer = value("--layer")?;
+                    parsed.layers.push(layer.parse().map_err(|_| format!("invalid layer \"{layer}\""))?);
+                }
+                "--sim-time" => {
+                    let time = value("--sim-time")?;
+                    let time: f32 = time.parse().map_err(|_| format!("invalid simulation time \"{time}\""))?;
+                    if !time.is_finite() || time <= 0.0 {
+                        return Err(String::from("--sim-time must be a positive number"))
+                    }
+                    parsed.sim_time = Some(time);
+                }
+                flag if flag.starts_with('-') => return Err(format!("unknown option \"{flag}\"")),
+                _ => positional.push(arg)
+            }
+        }
+        if parsed.command == Command::Help {
+            return Ok(parsed)
+        }
+        let mut positional = positional.into_iter();
+        match positional.next() {
+            Some(first) => match Command::from(&first) {
+                Some(command) => {
+                    parsed.command = command;
+                    parsed.config_path = positional.next();
+                }
+                None => {
+                    parsed.config_path = Some(first);
+                    if let Some(second) = positional.next() {
+                        parsed.command = match Command::from(&second) {
+                            Some(command @ (Command::Restore | Command::List | Command::Clean)) => command,
+                            _ => return Err(format!("unknown command \"{second}\""))
+                        };
+                    }
+                }
+            }
+            None => parsed.command = Command::Help
+        }
+        if parsed.command == Command::Export {
+            parsed.export_path = positional.next();
+        }
+        if let Some(extra) = positional.next() {
+            return Err(format!("unexpected argument \"{extra}\""))
+        }
+        if parsed.command != Command::Help && parsed.config_path.is_none() {
+            return Err(String::from("missing the config file"))
+        }
+        Ok(parsed)
+    }
+    /// Applies the overrides to a config
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        if let Some(level) = &self.level {
+            // The config's other selectors were meant for its own level
+            config.level_name = Some(level.clone());
+            config.level_index = None;
+            config.level_revision = None;
+            config.level_position = None;
+        }
+        if let Some(save) = &self.save {
+            config.path = save.clone();
+        }
+        if !self.layers.is_empty() {
+            if let Some(layer) = self.layers.iter().find(|l| !config.simulations.iter().any(|s| s.layer == **l)) {
+                return Err(format!("there is no simulation for layer {layer}"))
+            }
+            config.simulations.retain(|s| self.layers.contains(&s.layer));
+        }
+        if let Some(time) = self.sim_time {
+            for simulation in config.simulations.iter_mut() {
+                simulation.sim_time = time;
+            }
+        }
+        Ok(())
+    }
+}
+/// The help text, including the config parameters
+pub fn help() -> String {
+    let mut help = String::from("\
+Usage: gdphysics [command] <config.json> [options]
+
+Commands:
+  simulate          Runs the simulations and writes the level (default)
+  restore           Copies the backup over the save file
+  list              Lists the levels in the save file
+  inspect           Shows the level's settings, bodies and start positions
+  clean             Removes everything previous runs generated
+  export [file]     Writes the level to a .gmd or .txt file, output_path by default
+  help              Shows this message
+
+Options:
+  --level <name>    Uses the level with this name
+  --layer <layer>   Only runs the simulation for this layer, can be repeated
+  --sim-time <s>    Overrides the simulation time of every layer
+  --save <path>     Uses this save file instead of path
+  --dry-run         Reports what would change without writing anything
+  -h, --help        Shows this message
+");
+    write_params(&mut help, "Config parameters", CONFIG_PARAMS);
+    write_params(&mut help, "Simulation parameters (simulations)", LAYER_PARAMS);
+    write_params(&mut help, "Body parameters (objects)", OBJECT_PARAMS);
+    let _ = writeln!(help, "\nBuilt-in materials:");
+    for (name, material) in builtin_materials() {
+        let params: Vec<String> = material.as_object().into_iter().flatten()
+            .map(|(key, value)| format!("{key} {value}"))
+            .collect();
+        let _ = writeln!(help, "  {name:12}  {0}", params.join(", "));
+    }
+    help
+}
+fn write_params(help: &mut String, title: &str, params: &[Param]) {
+    let width = params.iter().map(|p| p.name.len() + p.kind.len() + 3).max().unwrap_or(0);
+    let _ = writeln!(help, "\n{title}:");
+    for p in params {
+        let name = format!("{0} ({1})", p.name, p.kind);
+        if p.default == "required" {
+            let _ = writeln!(help, "  {name:width$}  {0}, required", p.description);
+        }
+        else {
+            let _ = writeln!(help, "  {name:width$}  {0}, default {1}", p.description, p.default);
+        }
+    }
+}
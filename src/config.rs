@@ -1,15 +1,50 @@
 use std::fs::File;
-use std::io::{Read, Error};
+use std::io::{Read, Error, ErrorKind};
+use std::path::{Path, PathBuf};
 use serde_derive::{Serialize, Deserialize};
-use serde_json::{self, json};
+use serde_json::{self, json, Map, Value};
+use std::fmt;
+use crate::{levelstring::{LevelSelector, SaveFormat}, physics::OutputMode, speed::Speed};
+/// The top level configuration for a run
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct Config {    
-    pub level_name: String,    
+    #[serde(default)]
+    pub level_name: Option<String>,
+    /// Picks one of several levels sharing `level_name`, starting at 0
+    #[serde(default)]
+    pub level_index: Option<usize>,
+    /// Picks the level named `level_name` with this revision
+    #[serde(default)]
+    pub level_revision: Option<i32>,
+    /// Picks the level at this position in the save file instead of by name
+    #[serde(default)]
+    pub level_position: Option<usize>,
     pub path: String,
     pub backup_path: String,
-    pub simulations: Vec<LayerConfig>    
+    /// Writes the result here instead of overwriting `path`, as a `.gmd` if it ends in `.gmd`
+    /// and as a plain level string if it ends in `.txt`
+    #[serde(default)]
+    pub output_path: Option<String>,
+    pub simulations: Vec<LayerConfig>,
+    /// Overrides the save format detected from the file
+    #[serde(default)]
+    pub save_format: Option<SaveFormat>,
+    /// Config files this one is based on, relative to this file. Later files and this file
+    /// override earlier ones
+    #[serde(default)]
+    pub extends: Vec<String>,
+    /// Simulation and body parameters used by every simulation and body that doesn't set them
+    #[serde(default)]
+    pub defaults: Map<String, Value>,
+    /// Named sets of body parameters that bodies use with `material`, added to and
+    /// overriding the built-in materials
+    #[serde(default)]
+    pub materials: Map<String, Value>
 }
+/// The configuration of one simulation, which operates on a single editor layer
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct LayerConfig {
     pub layer: u16,
     #[serde(default = "default_height")]
@@ -29,9 +64,47 @@ pub struct LayerConfig {
     #[serde(default = "default_anchor")]
     pub anchor_id: i32,
     #[serde(default = "default_ground")]
-    pub ground: bool
+    pub ground: bool,
+    /// Overrides the starting speed from the level header or start position
+    #[serde(default)]
+    pub start_speed: Option<Speed>,
+    /// Times the triggers for playing from this start position instead of the start of the
+    /// level, counting from the left starting at 0
+    #[serde(default)]
+    pub start_pos: Option<usize>,
+    /// Body parameters used by every body on this layer that doesn't set them, including
+    /// bodies without an entry in `objects`
+    #[serde(default)]
+    pub object_defaults: Map<String, Value>,
+    /// Whether the triggers are placed along the level or spawned in time
+    #[serde(default)]
+    pub output_mode: OutputMode,
+    /// In platformer mode, the group a touch or collision trigger placed by the creator spawns
+    /// to start the motion
+    #[serde(default)]
+    pub start_group: Option<i32>,
+    /// In platformer mode without `start_group`, where the touch triggered start trigger is
+    /// placed instead of at the anchor
+    #[serde(default)]
+    pub start_trigger_pos: Option<[f32; 2]>,
+    /// Reads body parameters from text objects on the layer in each body's group, which are
+    /// left out of the body's shape
+    #[serde(default)]
+    pub text_config: bool,
+    /// Only simulates the bodies with these main groups, leaving other objects on the layer
+    /// alone. Every body on the layer is simulated if neither this nor `group_range` is set
+    #[serde(default)]
+    pub groups: Vec<u16>,
+    /// Only simulates the bodies with main groups from the first to the second, inclusive
+    #[serde(default)]
+    pub group_range: Option<[u16; 2]>,
+    /// The editor layer the generated triggers are placed on, `layer` by default
+    #[serde(default)]
+    pub trigger_layer: Option<u16>
 }
+/// The physical properties of one body, identified by its main group
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct ObjectConfig {
     pub group: u16,
     #[serde(default = "default_vel")]
@@ -54,24 +127,533 @@ pub struct ObjectConfig {
     pub position_fixed: bool,
     #[serde(default = "default_ang_fixed")]
     pub rotation_fixed: bool,    
+    /// The material the body's other parameters default to, applied when the config is loaded
+    #[serde(default)]
+    pub material: Option<String>
 }
 impl Config {
+    /// Loads a config from a JSON file and checks that it selects a level
     pub fn new(path: String) -> Result<Self, Error> {
-        let mut file = File::open(path)?;
-        let mut json_str = String::new();
-        file.read_to_string(&mut json_str)?;
-        let config: Config = serde_json::from_str(&json_str)?;
+        let config = Config::load(path)?;
+        config.check()?;
         Ok(config)        
     }
+    /// Loads a config from a JSON file without checking that it selects a level, so it can be
+    /// changed first
+    ///
+    /// Every problem found by `validate` is reported in the error, one per line
+    pub fn load(path: String) -> Result<Self, Error> {
+        let mut value = load_value(Path::new(&path), &mut Vec::new())?;
+        let problems = validate(&value);
+        if !problems.is_empty() {
+            let message: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+            return Err(Error::new(ErrorKind::InvalidData, message.join("\n")));
+        }
+        apply_defaults(&mut value);
+        let config: Config = serde_json::from_value(value)?;
+        Ok(config)
+    }
+    /// The built-in materials merged with `materials`, which bodies can name in text objects
+    pub fn material_library(&self) -> Map<String, Value> {
+        materials(&json!({ "materials": self.materials }))
+    }
+    /// Checks that the config selects a level
+    pub fn check(&self) -> Result<(), Error> {
+        // A .gmd or level string only holds one level, so it doesn't need to be selected
+        let single_level = is_gmd(&self.path) || is_level_string(&self.path);
+        if self.level_name.is_none() && self.level_position.is_none() && !single_level {
+            return Err(Error::new(ErrorKind::InvalidData, "either \"level_name\" or \"level_position\" must be set"));
+        }
+        Ok(())
+    }
+    /// The level selected by `level_name`, `level_index`, `level_revision` and `level_position`
+    pub fn level_selector(&self) -> LevelSelector {
+        match (&self.level_name, self.level_index, self.level_revision, self.level_position) {
+            (_, _, _, Some(position)) => LevelSelector::Position(position),
+            (Some(name), Some(index), _, _) => LevelSelector::NameIndex(name.clone(), index),
+            (Some(name), _, Some(revision), _) => LevelSelector::NameRevision(name.clone(), revision),
+            (Some(name), _, _, _) => LevelSelector::Name(name.clone()),
+            (None, _, _, None) => LevelSelector::Position(0)
+        }
+    }
+}
+/// Whether a path points to a `.gmd` level file
+pub fn is_gmd(path: &str) -> bool {
+    path.to_lowercase().ends_with(".gmd")
+}
+/// Whether a path points to a plain level string file
+pub fn is_level_string(path: &str) -> bool {
+    path.to_lowercase().ends_with(".txt")
+}
+impl LayerConfig {
+    /// Whether the body with this main group is simulated
+    pub fn selects(&self, group: i32) -> bool {
+        let in_range = self.group_range.is_some_and(|[first, last]| (first as i32..=last as i32).contains(&group));
+        let listed = self.groups.iter().any(|g| *g as i32 == group);
+        in_range || listed || self.groups.is_empty() && self.group_range.is_none()
+    }
+    /// The editor layer the generated triggers are placed on
+    pub fn trigger_layer(&self) -> u16 {
+        self.trigger_layer.unwrap_or(self.layer)
+    }
 }
 impl ObjectConfig {
+    /// Creates a config with default values for the given group
     pub fn new(group: u16) -> Self {
         serde_json::from_value(json!({
             "group" : group
         })).unwrap()        
     }
+    /// Creates a config for the given group from a layer's `object_defaults`, falling back
+    /// to the default values if they are invalid
+    pub fn with_defaults(group: u16, defaults: &Map<String, Value>) -> Self {
+        let mut value = Value::Object(defaults.clone());
+        value["group"] = json!(group);
+        serde_json::from_value(value).unwrap_or_else(|_| ObjectConfig::new(group))
+    }
+    /// This config with the parameters written in a text object in the level, which take
+    /// priority over it, followed by the material the text names
+    pub fn with_text(&self, text: &str, materials: &Map<String, Value>) -> Result<ObjectConfig, Vec<ConfigProblem>> {
+        let mut params = parse_object_text(text)?;
+        let mut problems = Vec::new();
+        check_material(&Value::Object(params.clone()), "text", materials, &mut problems);
+        if !problems.is_empty() {
+            return Err(problems)
+        }
+        apply_material(&mut params, materials);
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        merge(&mut value, Value::Object(params));
+        serde_json::from_value(value).map_err(|e| vec![ConfigProblem { path: String::from("text"), message: e.to_string() }])
+    }
+}
+/// Reads body parameters written as `key=value` pairs separated by spaces, new lines or
+/// semicolons, such as `density=2 velocity=100,50 material=rubber`
+///
+/// Every problem found is returned, with paths starting at `text`
+pub fn parse_object_text(text: &str) -> Result<Map<String, Value>, Vec<ConfigProblem>> {
+    let params: Vec<Param> = OBJECT_PARAMS.iter().filter(|p| p.name != "group").map(Param::optional).collect();
+    let mut problems = Vec::new();
+    let mut fields = Map::new();
+    for pair in text.split(|c: char| c.is_whitespace() || c == ';').filter(|p| !p.is_empty()) {
+        let Some((key, raw)) = pair.split_once('=') else {
+            problems.push(ConfigProblem { path: String::from("text"), message: format!("expected key=value, found \"{pair}\"") });
+            continue
+        };
+        // Values that don't parse are kept as text, so validation reports them
+        let text_value = Value::String(raw.to_string());
+        let value = match params.iter().find(|p| p.name == key).map(|p| p.kind) {
+            Some("float") => raw.parse::<f64>().ok().map(Value::from),
+            Some("bool") => raw.parse::<bool>().ok().map(Value::from),
+            Some("[float, float]") => match raw.split_once(',').map(|(x, y)| (x.parse::<f64>(), y.parse::<f64>())) {
+                Some((Ok(x), Ok(y))) => Some(json!([x, y])),
+                _ => None
+            },
+            _ => None
+        };
+        fields.insert(key.to_string(), value.unwrap_or(text_value));
+    }
+    let fields = Value::Object(fields);
+    validate_params(&fields, &params, "text", &mut problems);
+    match fields {
+        Value::Object(fields) if problems.is_empty() => Ok(fields),
+        _ => Err(problems)
+    }
+}
+/// Reads a config file and merges it over the files it extends
+///
+/// `chain` holds the files being loaded, to catch files that extend each other
+fn load_value(path: &Path, chain: &mut Vec<PathBuf>) -> Result<Value, Error> {
+    let in_file = |e: Error| Error::new(e.kind(), format!("{0}: {1}", path.display(), e));
+    let canonical = path.canonicalize().map_err(in_file)?;
+    if chain.contains(&canonical) {
+        return Err(in_file(Error::new(ErrorKind::InvalidData, "config files extend each other in a loop")));
+    }
+    let mut file = File::open(path).map_err(in_file)?;
+    let mut json_str = String::new();
+    file.read_to_string(&mut json_str).map_err(in_file)?;
+    let value: Value = serde_json::from_str(&json_str).map_err(|e| in_file(e.into()))?;
+    let bases = match value.get("extends") {
+        None => Vec::new(),
+        Some(Value::String(base)) => vec![base.clone()],
+        Some(Value::Array(bases)) if bases.iter().all(Value::is_string) => {
+            bases.iter().filter_map(|b| b.as_str().map(String::from)).collect()
+        }
+        Some(_) => return Err(in_file(Error::new(ErrorKind::InvalidData, "\"extends\" must be a file or a list of files")))
+    };
+    chain.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = Value::Object(Map::new());
+    for base in &bases {
+        merge(&mut merged, load_value(&dir.join(base), chain)?);
+    }
+    chain.pop();
+    merge(&mut merged, value);
+    merged["extends"] = json!(bases);
+    Ok(merged)
+}
+/// Merges `over` into `base`, merging objects field by field and replacing everything else
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over
+    }
+}
+/// The materials that come with the tool
+pub fn builtin_materials() -> Map<String, Value> {
+    serde_json::from_str(include_str!("materials.json")).unwrap()
+}
+/// The built-in materials merged with the config's `materials`
+fn materials(value: &Value) -> Map<String, Value> {
+    let mut materials = Value::Object(builtin_materials());
+    if let Some(custom @ Value::Object(_)) = value.get("materials") {
+        merge(&mut materials, custom.clone());
+    }
+    match materials {
+        Value::Object(materials) => materials,
+        _ => Map::new()
+    }
+}
+/// Fills the parameters a body doesn't set from its material
+fn apply_material(object: &mut Map<String, Value>, materials: &Map<String, Value>) {
+    let material = object.get("material").and_then(Value::as_str).and_then(|m| materials.get(m));
+    if let Some(Value::Object(material)) = material.cloned() {
+        for (key, param) in material {
+            object.entry(key).or_insert(param);
+        }
+    }
+}
+/// Copies `defaults` into every simulation, and the body parameters into every simulation's
+/// `object_defaults` and from there into every body, without replacing values that are set
+///
+/// Each block's own parameters come before its material, and a body comes before its layer's
+/// `object_defaults`, which come before `defaults`
+fn apply_defaults(value: &mut Value) {
+    let materials = materials(value);
+    let mut defaults = value.get("defaults").and_then(Value::as_object).cloned().unwrap_or_default();
+    apply_material(&mut defaults, &materials);
+    let Some(Value::Array(simulations)) = value.get_mut("simulations") else {
+        return
+    };
+    for simulation in simulations.iter_mut().filter_map(Value::as_object_mut) {
+        let mut object_defaults = simulation.get("object_defaults").and_then(Value::as_object).cloned().unwrap_or_default();
+        apply_material(&mut object_defaults, &materials);
+        for (key, default) in &defaults {
+            if OBJECT_PARAMS.iter().any(|p| p.name == key) {
+                object_defaults.entry(key.clone()).or_insert_with(|| default.clone());
+            }
+            else {
+                simulation.entry(key.clone()).or_insert_with(|| default.clone());
+            }
+        }
+        if let Some(Value::Array(objects)) = simulation.get_mut("objects") {
+            for object in objects.iter_mut().filter_map(Value::as_object_mut) {
+                apply_material(object, &materials);
+                for (key, default) in &object_defaults {
+                    object.entry(key.clone()).or_insert_with(|| default.clone());
+                }
+            }
+        }
+        simulation.insert(String::from("object_defaults"), Value::Object(object_defaults));
+    }
+}
+/// A problem with a config value
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigProblem {
+    /// Where the problem is, e.g. `simulations[0].objects[1].velocity`
+    pub path: String,
+    pub message: String
+}
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{0}: {1}", self.path, self.message)
+    }
+}
+/// Checks a config for unknown fields, missing fields, wrong types and values out of range,
+/// returning every problem found
+pub fn validate(value: &Value) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    validate_params(value, CONFIG_PARAMS, "", &mut problems);
+    if let Some(Value::Object(materials)) = value.get("materials") {
+        let params: Vec<Param> = OBJECT_PARAMS.iter()
+            .filter(|p| !matches!(p.name, "group" | "material"))
+            .map(Param::optional)
+            .collect();
+        for (name, material) in materials {
+            validate_params(material, &params, &format!("materials.{name}"), &mut problems);
+        }
+    }
+    let materials = materials(value);
+    if let Some(defaults @ Value::Object(_)) = value.get("defaults") {
+        let params: Vec<Param> = LAYER_PARAMS.iter().chain(OBJECT_PARAMS)
+            .filter(|p| p.default != "required" && !matches!(p.name, "objects" | "object_defaults" | "groups" | "group_range" | "trigger_layer"))
+            .map(Param::optional)
+            .collect();
+        validate_params(defaults, &params, "defaults", &mut problems);
+        check_material(defaults, "defaults", &materials, &mut problems);
+    }
+    if let Some(Value::Array(simulations)) = value.get("simulations") {
+        // Each run replaces the triggers on its trigger layer, so simulations can't share one
+        let mut trigger_layers: Vec<(u64, usize)> = Vec::new();
+        // A body simulated twice would get two sets of triggers
+        let mut selections: Vec<(Selection, usize)> = Vec::new();
+        for (i, simulation) in simulations.iter().enumerate() {
+            let path = format!("simulations[{i}]");
+            validate_params(simulation, LAYER_PARAMS, &path, &mut problems);
+            if let Some(selection) = Selection::from(simulation) {
+                let shared = selections.iter().find_map(|(other, j)| Some((selection.shared(other)?, j)));
+                if let Some((shared, first)) = shared {
+                    problems.push(ConfigProblem {
+                        path: format!("{path}.groups"),
+                        message: format!("simulations[{first}] already simulates {shared} on layer {0}, select different groups", selection.layer)
+                    });
+                }
+                selections.push((selection, i));
+            }
+            let trigger_layer = simulation.get("trigger_layer")
+                .or_else(|| simulation.get("layer"))
+                .and_then(Value::as_u64);
+            if let Some(layer) = trigger_layer {
+                match trigger_layers.iter().find(|(l, _)| *l == layer) {
+                    Some((_, first)) => problems.push(ConfigProblem {
+                        path: format!("{path}.trigger_layer"),
+                        message: format!("simulations[{first}] already places its triggers on layer {layer}, set a different trigger_layer")
+                    }),
+                    None => trigger_layers.push((layer, i))
+                }
+            }
+            if let Some(Value::Array(range)) = simulation.get("group_range") {
+                if let [Some(first), Some(last)] = [range.first(), range.get(1)].map(|g| g.and_then(Value::as_u64)) {
+                    if first > last {
+                        problems.push(ConfigProblem { path: format!("{path}.group_range"), message: String::from("the first group must not be above the last") });
+                    }
+                }
+            }
+            if let Some(object_defaults @ Value::Object(_)) = simulation.get("object_defaults") {
+                let params: Vec<Param> = OBJECT_PARAMS.iter().filter(|p| p.name != "group").map(Param::optional).collect();
+                let path = format!("{path}.object_defaults");
+                validate_params(object_defaults, &params, &path, &mut problems);
+                check_material(object_defaults, &path, &materials, &mut problems);
+            }
+            if let Some(Value::Array(objects)) = simulation.get("objects") {
+                for (j, object) in objects.iter().enumerate() {
+                    let path = format!("{path}.objects[{j}]");
+                    validate_params(object, OBJECT_PARAMS, &path, &mut problems);
+                    check_material(object, &path, &materials, &mut problems);
+                }
+            }
+        }
+    }
+    problems
+}
+/// The bodies a simulation selects, read from its config before it's deserialized
+struct Selection {
+    layer: u64,
+    groups: Vec<u64>,
+    range: Option<(u64, u64)>
+}
+impl Selection {
+    fn from(simulation: &Value) -> Option<Selection> {
+        let groups = match simulation.get("groups") {
+            Some(Value::Array(groups)) => groups.iter().filter_map(Value::as_u64).collect(),
+            _ => Vec::new()
+        };
+        let range = match simulation.get("group_range") {
+            Some(Value::Array(range)) => Some((range.first()?.as_u64()?, range.get(1)?.as_u64()?)),
+            _ => None
+        };
+        Some(Selection { layer: simulation.get("layer")?.as_u64()?, groups, range })
+    }
+    fn selects(&self, group: u64) -> bool {
+        self.groups.contains(&group) || self.range.is_some_and(|(first, last)| (first..=last).contains(&group))
+            || self.groups.is_empty() && self.range.is_none()
+    }
+    /// The lowest group both selections simulate, described for an error message
+    fn shared(&self, other: &Selection) -> Option<String> {
+        if self.layer != other.layer {
+            return None
+        }
+        let candidates = [self, other].into_iter()
+            .flat_map(|s| s.groups.iter().copied().chain(s.range.map(|(first, _)| first)));
+        match candidates.filter(|g| self.selects(*g) && other.selects(*g)).min() {
+            Some(group) => Some(format!("group {group}")),
+            None if self.selects(u64::MAX) && other.selects(u64::MAX) => Some(String::from("every group")),
+            None => None
+        }
+    }
+}
+fn validate_params(value: &Value, params: &[Param], path: &str, problems: &mut Vec<ConfigProblem>) {
+    let mut problem = |path: String, message: String| problems.push(ConfigProblem { path, message });
+    let child = |name: &str| if path.is_empty() {name.to_string()} else {format!("{path}.{name}")};
+    let Value::Object(fields) = value else {
+        problem(path.to_string(), String::from("must be an object"));
+        return
+    };
+    for (name, value) in fields {
+        let Some(param) = params.iter().find(|p| p.name == name) else {
+            let names: Vec<&str> = params.iter().map(|p| p.name).collect();
+            let message = match suggest(name, &names) {
+                Some(suggestion) => format!("unknown field \"{name}\", did you mean \"{suggestion}\"?"),
+                None => format!("unknown field \"{name}\"")
+            };
+            problem(child(name), message);
+            continue
+        };
+        // Optional fields can be left null
+        let null_allowed = value.is_null() && param.default != "required";
+        if !null_allowed && !kind_matches(param.kind, value) {
+            problem(child(name), format!("expected {0}, found {1}", param.kind, value));
+        }
+        else if let Some(message) = check_range(name, value) {
+            problem(child(name), message);
+        }
+    }
+    for param in params.iter().filter(|p| p.default == "required") {
+        if !fields.contains_key(param.name) {
+            problem(child(param.name), String::from("missing required field"));
+        }
+    }
+}
+fn check_material(body: &Value, path: &str, materials: &Map<String, Value>, problems: &mut Vec<ConfigProblem>) {
+    let Some(Value::String(name)) = body.get("material") else { return };
+    if materials.contains_key(name) {
+        return
+    }
+    let names: Vec<&str> = materials.keys().map(String::as_str).collect();
+    let message = match suggest(name, &names) {
+        Some(suggestion) => format!("unknown material \"{name}\", did you mean \"{suggestion}\"?"),
+        None => format!("unknown material \"{name}\", the materials are {}", names.join(", "))
+    };
+    problems.push(ConfigProblem { path: format!("{path}.material"), message });
+}
+fn kind_matches(kind: &str, value: &Value) -> bool {
+    match kind {
+        "string" => value.is_string(),
+        "integer" => value.is_u64(),
+        "float" => value.is_number(),
+        "bool" => value.is_boolean(),
+        "list" => value.is_array(),
+        "[float, float]" => matches!(value, Value::Array(a) if a.len() == 2 && a.iter().all(Value::is_number)),
+        "[integer, integer]" => matches!(value, Value::Array(a) if a.len() == 2 && a.iter().all(Value::is_u64)),
+        "list of integers" => matches!(value, Value::Array(a) if a.iter().all(Value::is_u64)),
+        "object" => value.is_object(),
+        "string or list" => value.is_string() || matches!(value, Value::Array(a) if a.iter().all(Value::is_string)),
+        _ => true
+    }
+}
+fn check_range(name: &str, value: &Value) -> Option<String> {
+    let value = value.as_f64()?;
+    match name {
+        "fps" | "sim_time" | "density" if value <= 0.0 => Some(String::from("must be greater than 0")),
+        "keyframe_interval" if value < 1.0 => Some(String::from("must be at least 1")),
+        _ => None
+    }
+}
+/// The known name closest to a misspelled one
+fn suggest<'a>(name: &str, names: &[&'a str]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    names.iter()
+        .map(|known| {
+            // Extra words around a known name, like "initial_velocity", count as close
+            let contained = name.len().min(known.len()) >= 4 && (name.contains(known) || known.contains(name.as_str()));
+            let distance = if contained {0} else {edit_distance(&name, known)};
+            (distance, *known)
+        })
+        .filter(|(distance, param)| *distance <= param.len() / 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, param)| param)
+}
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + if ca == *cb {0} else {1};
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+/// Describes a config parameter for the help text and validation
+pub struct Param {
+    pub name: &'static str,
+    pub kind: &'static str,
+    pub default: &'static str,
+    pub description: &'static str
+}
+impl Param {
+    /// The same parameter, which doesn't need to be set
+    fn optional(&self) -> Param {
+        Param { default: "inherited", ..*self }
+    }
+}
+const fn param(name: &'static str, kind: &'static str, default: &'static str, description: &'static str) -> Param {
+    Param { name, kind, default, description }
 }
-// Todo: move defaults to another config file
+/// The parameters of `Config`
+pub const CONFIG_PARAMS: &[Param] = &[
+    param("level_name", "string", "none", "The name of the level to edit"),
+    param("level_index", "integer", "none", "Picks one of several levels sharing level_name, starting at 0"),
+    param("level_revision", "integer", "none", "Picks the level named level_name with this revision"),
+    param("level_position", "integer", "none", "Picks the level at this position in the save instead of by name"),
+    param("path", "string", "required", "The save file, .gmd or level string to read"),
+    param("backup_path", "string", "required", "Where the save is backed up before writing"),
+    param("output_path", "string", "none", "Writes the result here instead, as a .gmd or level string if it ends in .gmd or .txt"),
+    param("save_format", "string", "detected", "windows, mac, plain or levelstring"),
+    param("simulations", "list", "required", "The simulations to run"),
+    param("extends", "string or list", "none", "Config files this one is based on, relative to it"),
+    param("defaults", "object", "none", "Simulation and body parameters for every simulation and body that doesn't set them"),
+    param("materials", "object", "none", "Named sets of body parameters, added to the built-in materials"),
+];
+/// The parameters of `LayerConfig`
+pub const LAYER_PARAMS: &[Param] = &[
+    param("layer", "integer", "required", "The editor layer of the bodies, and of the generated triggers unless trigger_layer is set"),
+    param("height", "float", "2100", "The height triggers are placed at"),
+    param("fps", "float", "60", "Frames per second of the simulation"),
+    param("keyframe_interval", "integer", "6", "Frames between each keyframe"),
+    param("gravity", "[float, float]", "[0, -294.3]", "The gravity vector"),
+    param("rotation_duration_modifier", "float", "0.9", "Multiplier for the duration of rotate triggers"),
+    param("sim_time", "float", "5", "Length of the simulation in seconds"),
+    param("anchor_id", "integer", "41", "Object id of the anchor that marks where the motion starts"),
+    param("ground", "bool", "true", "Whether there is ground at y = 0"),
+    param("start_speed", "string", "level setting", "Overrides the starting speed, half, normal, double, triple or quad"),
+    param("start_pos", "integer", "none", "Times the triggers for playing from this start position"),
+    param("output_mode", "string", "position", "position, spawn or platformer"),
+    param("start_group", "integer", "none", "In platformer mode, the group your own trigger spawns to start the motion"),
+    param("start_trigger_pos", "[float, float]", "anchor", "In platformer mode, where the touch triggered start trigger goes"),
+    param("objects", "list", "empty", "Per body parameters"),
+    param("object_defaults", "object", "none", "Body parameters for every body on this layer that doesn't set them"),
+    param("text_config", "bool", "false", "Reads body parameters from text objects in each body's group"),
+    param("groups", "list of integers", "every group", "Only simulates the bodies with these main groups"),
+    param("group_range", "[integer, integer]", "every group", "Only simulates the bodies with main groups in this range, inclusive"),
+    param("trigger_layer", "integer", "layer", "The editor layer the generated triggers are placed on"),
+];
+/// The parameters of `ObjectConfig`
+pub const OBJECT_PARAMS: &[Param] = &[
+    param("group", "integer", "required", "The main group of the body"),
+    param("velocity", "[float, float]", "[0, 0]", "The initial velocity"),
+    param("angular_velocity", "float", "0", "The initial angular velocity in degrees per second"),
+    param("density", "float", "1", "The density"),
+    param("dynamic", "bool", "true", "Whether the body moves"),
+    param("restitution", "float", "0", "The coefficient of restitution"),
+    param("linear_damping", "float", "0.1", "The damping of linear motion"),
+    param("angular_damping", "float", "0.1", "The damping of rotation"),
+    param("friction", "float", "1", "The friction coefficient"),
+    param("position_fixed", "bool", "false", "Whether the position is fixed"),
+    param("rotation_fixed", "bool", "false", "Whether the rotation is fixed"),
+    param("material", "string", "none", "A material whose parameters are used where the body doesn't set them"),
+];
 fn default_fps() -> f32 {60.0}
 fn default_interval() -> u16 {6}
 fn default_vel() -> [f32; 2] {[0.0, 0.0]}
@@ -0,0 +1,96 @@
+use std::fmt;
+use crate::levelstring::LevelSelector;
+
+/// Errors that can occur while reading or writing levels
+#[derive(Debug)]
+pub enum GdError {
+    /// Reading or writing a file failed
+    Io(std::io::Error),
+    /// A save file or level string wasn't valid base64
+    Base64(base64::DecodeError),
+    /// A save file or level string couldn't be (de)compressed
+    Gzip(std::io::Error),
+    /// A macOS save file couldn't be decrypted
+    Decrypt(String),
+    /// The save file's XML couldn't be read or written
+    Xml(quick_xml::Error),
+    /// Decrypted data wasn't valid UTF-8
+    Utf8(std::string::FromUtf8Error),
+    /// No level matches the selector
+    LevelNotFound(LevelSelector),
+    /// Several levels share the name, `candidates` holds their positions and revisions
+    AmbiguousLevel {
+        name: String,
+        candidates: Vec<(usize, Option<i32>)>
+    },
+    /// The level exists but has no level string yet
+    LevelUninitialized(String),
+    /// An object attribute couldn't be parsed. `index` is the position of the object in the level
+    ObjectParse {
+        index: usize,
+        key: u16,
+        value: String
+    }
+}
+impl fmt::Display for GdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GdError::Io(e) => write!(f, "{e}"),
+            GdError::Base64(e) => write!(f, "invalid base64: {e}"),
+            GdError::Gzip(e) => write!(f, "invalid compressed data: {e}"),
+            GdError::Decrypt(e) => write!(f, "failed to decrypt: {e}"),
+            GdError::Xml(e) => write!(f, "invalid XML: {e}"),
+            GdError::Utf8(e) => write!(f, "invalid UTF-8: {e}"),
+            GdError::LevelNotFound(selector) => write!(f, "Level {selector} was not found!"),
+            GdError::AmbiguousLevel { name, candidates } => {
+                write!(f, "{0} levels are named \"{name}\":", candidates.len())?;
+                for (position, revision) in candidates {
+                    match revision {
+                        Some(revision) => write!(f, " position {position} (revision {revision}),")?,
+                        None => write!(f, " position {position},")?
+                    }
+                }
+                write!(f, " set \"level_index\", \"level_revision\" or \"level_position\" to pick one")
+            },
+            GdError::LevelUninitialized(name) => write!(
+                f,
+                "Level \"{name}\" is not initialized! Please open the level, place some objects, then save and quit to initialize the level."
+            ),
+            GdError::ObjectParse { index, key, value } => write!(
+                f,
+                "Failed to parse key {key} with value \"{value}\" of object {index}"
+            )
+        }
+    }
+}
+impl std::error::Error for GdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GdError::Io(e) | GdError::Gzip(e) => Some(e),
+            GdError::Base64(e) => Some(e),
+            GdError::Xml(e) => Some(e),
+            GdError::Utf8(e) => Some(e),
+            _ => None
+        }
+    }
+}
+impl From<std::io::Error> for GdError {
+    fn from(e: std::io::Error) -> Self {
+        GdError::Io(e)
+    }
+}
+impl From<base64::DecodeError> for GdError {
+    fn from(e: base64::DecodeError) -> Self {
+        GdError::Base64(e)
+    }
+}
+impl From<quick_xml::Error> for GdError {
+    fn from(e: quick_xml::Error) -> Self {
+        GdError::Xml(e)
+    }
+}
+impl From<std::string::FromUtf8Error> for GdError {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        GdError::Utf8(e)
+    }
+}
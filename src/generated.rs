@@ -0,0 +1,113 @@
+use crate::object::{*, AttribKey::*, AttribValue::*};
+use std::collections::BTreeSet;
+
+const TEXT_OBJECT: i32 = 914;
+/// The move, rotate and spawn triggers, which versions before the marker generated untagged
+const LEGACY_TRIGGERS: [i32; 3] = [901, 1346, 1268];
+/// The text of the marker object, followed by the group
+const MARKER_PREFIX: &str = "gdphysics generated group ";
+/// Where the marker is placed, before the start and far below the ground so it never shows in gameplay
+const MARKER_POS: (f32, f32) = (-90.0, -3000.0);
+
+/// Returns the group that tags the objects generated by the simulation, which is recorded in
+/// a marker text object in the level
+pub fn generated_group(objects: &[Obj]) -> Option<i32> {
+    objects.iter().find_map(marker_group)
+}
+/// Returns the generated group, adding a marker with a free group if the level has none
+pub fn ensure_generated_group(objects: &mut Vec<Obj>) -> i32 {
+    if let Some(group) = generated_group(objects) {
+        return group
+    }
+    let group = max_group(objects) + 1;
+    let mut marker = Obj::new(TEXT_OBJECT, MARKER_POS.0, MARKER_POS.1);
+    marker.set_text(&format!("{MARKER_PREFIX}{group}"));
+    objects.push(marker);
+    group
+}
+/// Adds an object to the generated group
+pub fn tag(mut obj: Obj, group: i32) -> Obj {
+    let mut groups = match obj.get(GroupIDs) {
+        Some(Array(groups)) => groups.clone(),
+        _ => Vec::new()
+    };
+    if !groups.contains(&group) {
+        groups.push(group);
+    }
+    obj.set(GroupIDs, Array(groups));
+    obj
+}
+/// Whether an object is in the generated group
+pub fn is_generated(obj: &Obj, group: i32) -> bool {
+    matches!(obj.get(GroupIDs), Some(Array(groups)) if groups.contains(&group))
+}
+/// Removes the generated objects on `layer`, or on every layer along with the marker, and
+/// returns how many objects were removed
+///
+/// Versions before the marker didn't tag their output, so in a level without a marker the
+/// linked move, rotate and spawn triggers on `layer` are removed instead
+pub fn clean(objects: &mut Vec<Obj>, layer: Option<u16>) -> usize {
+    let generated_group = generated_group(objects);
+    let len = objects.len();
+    objects.retain(|o| {
+        let on_layer = match (layer, o.get(EditorLayer1)) {
+            (None, _) => true,
+            (Some(layer), Some(Int(l))) => *l == layer as i32,
+            (Some(layer), _) => layer == 0
+        };
+        let remove = match generated_group {
+            Some(group) => on_layer && is_generated(o, group) || layer.is_none() && marker_group(o).is_some(),
+            None => layer.is_some() && on_layer && is_legacy(o)
+        };
+        !remove
+    });
+    len - objects.len()
+}
+/// The highest group used by any object in the level, including the generated group
+pub fn max_group(objects: &[Obj]) -> i32 {
+    let max = generated_group(objects).unwrap_or(0);
+    objects.iter().flat_map(groups).fold(max, i32::max)
+}
+/// The groups free for new generated objects, lowest first, above `floor` and every group
+/// used by objects that weren't generated
+///
+/// Groups still used by generated objects, such as another simulation's spawn chain, are
+/// skipped, and groups freed by cleaning are reused, so rerunning doesn't use up groups
+pub fn free_groups(objects: &[Obj], floor: i32) -> impl Iterator<Item = i32> {
+    let generated_group = generated_group(objects);
+    let mut used = BTreeSet::new();
+    let mut max = floor;
+    for object in objects {
+        if generated_group.is_some_and(|g| is_generated(object, g)) {
+            used.extend(groups(object));
+        }
+        else {
+            max = groups(object).fold(max, i32::max);
+        }
+    }
+    used.extend(generated_group);
+    (max + 1..).filter(move |g| !used.contains(g))
+}
+/// The groups an object is in or targets
+fn groups(object: &Obj) -> impl Iterator<Item = i32> + '_ {
+    let in_groups = match object.get(GroupIDs) {
+        Some(Array(groups)) => groups.as_slice(),
+        _ => &[]
+    };
+    let targets = [TargetGroupID, SecondaryGroupID].into_iter().filter_map(|key| match object.get(key) {
+        Some(Int(group)) => Some(*group),
+        _ => None
+    });
+    in_groups.iter().copied().chain(targets)
+}
+/// Whether an object looks like a trigger generated by a version before the marker, which
+/// linked all of its triggers
+fn is_legacy(obj: &Obj) -> bool {
+    matches!(obj.get(ObjID), Some(Int(id)) if LEGACY_TRIGGERS.contains(id)) && obj.get(LinkedGroupID).is_some()
+}
+fn marker_group(obj: &Obj) -> Option<i32> {
+    if !matches!(obj.get(ObjID), Some(Int(TEXT_OBJECT))) {
+        return None
+    }
+    obj.text()?.strip_prefix(MARKER_PREFIX)?.parse().ok()
+}
@@ -0,0 +1,235 @@
+use crate::speed::Speed;
+use serde_derive::{Serialize, Deserialize};
+
+/// The game mode the player starts in (`kA2`)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    Cube, Ship, Ball, Ufo, Wave, Robot, Spider, Swing
+}
+impl GameMode {
+    pub fn from(id: i32) -> Option<GameMode> {
+        match id {
+            0 => Some(GameMode::Cube),
+            1 => Some(GameMode::Ship),
+            2 => Some(GameMode::Ball),
+            3 => Some(GameMode::Ufo),
+            4 => Some(GameMode::Wave),
+            5 => Some(GameMode::Robot),
+            6 => Some(GameMode::Spider),
+            7 => Some(GameMode::Swing),
+            _ => None
+        }
+    }
+    pub fn id(&self) -> i32 {
+        *self as i32
+    }
+}
+
+/// One color channel of the level's `kS38` color list
+///
+/// Properties are stored as raw `key_value` pairs in their original order
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorChannel {
+    props: Vec<(String, String)>
+}
+impl ColorChannel {
+    /// Creates a white channel with the given id
+    pub fn new(id: i32) -> Self {
+        ColorChannel::from("1_255_2_255_3_255_11_255_12_255_13_255_4_-1_7_1_15_1_18_0_8_1")
+            .with("6", id.to_string())
+    }
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.props.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+    pub fn set(&mut self, key: &str, val: String) {
+        if let Some((_, v)) = self.props.iter_mut().find(|(k, _)| k == key) {
+            *v = val;
+        }
+        else {
+            self.props.push((key.to_string(), val));
+        }
+    }
+    pub fn with(mut self, key: &str, val: String) -> Self {
+        self.set(key, val);
+        self
+    }
+    /// The channel id, e.g. 1000 for the background
+    pub fn id(&self) -> Option<i32> {
+        self.get("6").and_then(|v| v.parse().ok())
+    }
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        let channel = |key| self.get(key).and_then(|v| v.parse().ok());
+        Some((channel("1")?, channel("2")?, channel("3")?))
+    }
+    pub fn set_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.set("1", r.to_string());
+        self.set("2", g.to_string());
+        self.set("3", b.to_string());
+    }
+    pub fn opacity(&self) -> Option<f32> {
+        self.get("7").and_then(|v| v.parse().ok())
+    }
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.set("7", opacity.to_string());
+    }
+    pub fn blending(&self) -> bool {
+        self.get("5") == Some("1")
+    }
+    pub fn set_blending(&mut self, blending: bool) {
+        self.set("5", if blending {"1"} else {"0"}.to_string());
+    }
+    pub fn as_str(&self) -> String {
+        self.props.iter()
+            .map(|(k, v)| format!("{k}_{v}"))
+            .collect::<Vec<String>>()
+            .join("_")
+    }
+}
+impl From<&str> for ColorChannel {
+    fn from(str: &str) -> Self {
+        let split: Vec<&str> = str.split('_').collect();
+        ColorChannel {
+            props: split.chunks(2)
+                .filter(|c| c.len() == 2)
+                .map(|c| (c[0].to_string(), c[1].to_string()))
+                .collect()
+        }
+    }
+}
+
+/// The first section of a level string, which holds the level settings
+///
+/// Settings are stored as raw key value pairs in their original order, so unknown settings
+/// survive re-serialization
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelHeader {
+    props: Vec<(String, String)>
+}
+impl LevelHeader {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.props.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+    pub fn set(&mut self, key: &str, val: String) {
+        if let Some((_, v)) = self.props.iter_mut().find(|(k, _)| k == key) {
+            *v = val;
+        }
+        else {
+            self.props.push((key.to_string(), val));
+        }
+    }
+    fn get_bool(&self, key: &str) -> bool {
+        self.get(key) == Some("1")
+    }
+    fn set_bool(&mut self, key: &str, val: bool) {
+        self.set(key, if val {"1"} else {"0"}.to_string());
+    }
+    /// The color channels (`kS38`)
+    pub fn colors(&self) -> Vec<ColorChannel> {
+        self.get("kS38").unwrap_or_default()
+            .split('|')
+            .filter(|c| !c.is_empty())
+            .map(ColorChannel::from)
+            .collect()
+    }
+    pub fn set_colors(&mut self, colors: &[ColorChannel]) {
+        let mut str = String::new();
+        for color in colors {
+            str.push_str(&color.as_str());
+            str.push('|');
+        }
+        self.set("kS38", str);
+    }
+    /// The color channel with the given id
+    pub fn color(&self, id: i32) -> Option<ColorChannel> {
+        self.colors().into_iter().find(|c| c.id() == Some(id))
+    }
+    /// Replaces the color channel with the same id, or adds it
+    pub fn set_color(&mut self, color: ColorChannel) {
+        let mut colors = self.colors();
+        if let Some(c) = colors.iter_mut().find(|c| c.id().is_some() && c.id() == color.id()) {
+            *c = color;
+        }
+        else {
+            colors.push(color);
+        }
+        self.set_colors(&colors);
+    }
+    /// The starting game mode (`kA2`)
+    pub fn game_mode(&self) -> GameMode {
+        self.get("kA2").and_then(|v| v.parse().ok()).and_then(GameMode::from).unwrap_or(GameMode::Cube)
+    }
+    pub fn set_game_mode(&mut self, mode: GameMode) {
+        self.set("kA2", mode.id().to_string());
+    }
+    /// The starting speed (`kA4`)
+    pub fn speed(&self) -> Speed {
+        self.get("kA4").and_then(|v| v.parse().ok()).and_then(Speed::from_header).unwrap_or(Speed::Normal)
+    }
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.set("kA4", speed.header_id().to_string());
+    }
+    /// The song offset in seconds (`kA13`)
+    pub fn song_offset(&self) -> f32 {
+        self.get("kA13").and_then(|v| v.parse().ok()).unwrap_or(0.0)
+    }
+    pub fn set_song_offset(&mut self, offset: f32) {
+        self.set("kA13", offset.to_string());
+    }
+    /// Whether the player starts mini (`kA3`)
+    pub fn mini(&self) -> bool {
+        self.get_bool("kA3")
+    }
+    pub fn set_mini(&mut self, mini: bool) {
+        self.set_bool("kA3", mini);
+    }
+    /// Whether the player starts in dual mode (`kA8`)
+    pub fn dual(&self) -> bool {
+        self.get_bool("kA8")
+    }
+    pub fn set_dual(&mut self, dual: bool) {
+        self.set_bool("kA8", dual);
+    }
+    /// Whether 2-player mode is enabled (`kA10`)
+    pub fn two_player(&self) -> bool {
+        self.get_bool("kA10")
+    }
+    pub fn set_two_player(&mut self, two_player: bool) {
+        self.set_bool("kA10", two_player);
+    }
+    /// Whether the level is a platformer, where the player moves freely (`kA22`)
+    pub fn platformer(&self) -> bool {
+        self.get_bool("kA22")
+    }
+    pub fn set_platformer(&mut self, platformer: bool) {
+        self.set_bool("kA22", platformer);
+    }
+    /// The raw guideline string (`kA14`)
+    pub fn guidelines(&self) -> &str {
+        self.get("kA14").unwrap_or_default()
+    }
+    pub fn set_guidelines(&mut self, guidelines: String) {
+        self.set("kA14", guidelines);
+    }
+    pub fn as_str(&self) -> String {
+        self.props.iter()
+            .map(|(k, v)| format!("{k},{v}"))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+impl From<&str> for LevelHeader {
+    fn from(str: &str) -> Self {
+        if str.is_empty() {
+            return LevelHeader::default();
+        }
+        let split: Vec<&str> = str.split(',').collect();
+        LevelHeader {
+            props: split.chunks(2)
+                // Ignore a trailing comma
+                .filter(|c| c.len() == 2 || !c[0].is_empty())
+                .map(|c| (c[0].to_string(), c.get(1).unwrap_or(&"").to_string()))
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{error::GdError, header::LevelHeader, object::Obj};
+
+/// A decompressed level string split into its header and objects
+pub struct Level {
+    pub header: LevelHeader,
+    pub objects: Vec<Obj>
+}
+impl Level {
+    /// Joins the header and objects back into a level string
+    pub fn as_str(&self) -> String {
+        let mut ls = self.header.as_str() + ";";
+        for obj in self.objects.iter() {
+            ls.push_str(&obj.as_str());
+            ls.push(';');
+        }
+        ls
+    }
+}
+impl TryFrom<String> for Level {
+    type Error = GdError;
+    fn try_from(ls: String) -> Result<Self, GdError> {
+        let mut split = ls.split(';');
+        let header = LevelHeader::from(split.next().unwrap_or_default());
+        // Skip the empty string left by the trailing semicolon
+        let objects = split
+            .filter(|s| !s.is_empty())
+            .enumerate()
+            .map(|(i, s)| Obj::try_from(s.to_string()).map_err(|e| match e {
+                GdError::ObjectParse { key, value, .. } => GdError::ObjectParse { index: i, key, value },
+                e => e
+            }))
+            .collect::<Result<_, _>>()?;
+        Ok(Level {
+            header,
+            objects
+        })
+    }
+}
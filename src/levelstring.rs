@@ -2,268 +2,470 @@
 
 use libflate::{gzip, zlib};
 use std::io::Read;
+use crate::error::GdError;
 
 fn xor(data: Vec<u8>, key: u8) -> Vec<u8> {
     data.into_iter().map(|b| b ^ key).collect()
 }
-fn base_64_decrypt(encoded: Vec<u8>) -> Vec<u8> {
+fn base_64_decrypt(encoded: Vec<u8>) -> Result<Vec<u8>, GdError> {
     let l = encoded.len();
-    base64::decode(
-        String::from_utf8([encoded, b"=".repeat(l % 4)].concat())
-            .unwrap()
+    Ok(base64::decode(
+        String::from_utf8([encoded, b"=".repeat(l % 4)].concat())?
             .as_str(),
-    )
-    .unwrap()
+    )?)
 }
 
-use quick_xml::events::{BytesText, Event};
+use serde_derive::{Serialize, Deserialize};
+use quick_xml::events::Event;
 use quick_xml::Reader;
-//use std::io::BufReader;
-fn decrypt_savefile(sf: Vec<u8>) -> Result<Vec<u8>, String> {
-    if cfg!(target_os = "macos") {
-        Err("No mac support".to_string())
-    } else {
-        let xor = xor(sf.to_vec(), 11);
-        let replaced = String::from_utf8_lossy(&xor)
-            .replace('-', "+")
-            .replace('_', "/")
-            .replace('\0', "");
-        let b64 = match base64::decode(replaced.as_str()) {
-            Ok(b) => b,
-            Err(e) => return Err(format!("{}", e)),
-        };
-        let mut decoder = gzip::Decoder::new(&b64[..]).unwrap();
-        let mut data = Vec::new();
-        decoder.read_to_end(&mut data).unwrap();
-        Ok(data)
-    }
+use std::fmt;
+use std::ops::Range;
+use aes::Aes256;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray};
+
+const MAC_KEY: &[u8; 32] = b"ipu9TUv54yv]isFMh5@;t.5w34E2Ry@{";
+
+/// The encryption a save file uses
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveFormat {
+    /// XOR with 11, URL-safe base64 and gzip, used on Windows and Android
+    Windows,
+    /// AES-256 in ECB mode with PKCS7 padding, used on macOS
+    Mac,
+    /// Unencrypted plist XML
+    Plain
 }
-pub fn get_level_string(ls: Vec<u8>, level_name: Option<&String>) -> Result<String, String> {
-    //decrypting the savefile
-    let content = decrypt_savefile(ls)?;
-    let string_content = String::from_utf8_lossy(&content);
-
-    let mut reader = Reader::from_str(&string_content);
-    reader.trim_text(true);
-
-    let mut buf = Vec::new();
-
-    // The `Reader` does not implement `Iterator` because it outputs borrowed data (`Cow`s)
-    let mut level_string = String::new();
-    let mut k4_detected = false;
-    let mut k2_detected = false;
-    let mut level_detected = false;
-
-    loop {
-        match reader.read_event(&mut buf) {
-            // unescape and decode the text event using the reader encoding
-            Ok(Event::Text(e)) => {
-                let text = e.unescape_and_decode(&reader).unwrap();
-
-                if text == "k2" {
-                    k2_detected = true;
-                    if level_detected {
-                        return Err(
-                            "Level is not initialized! Please open the level, place some objects, then save and quit to initialize the level."
-                            .to_string()
-                        );
-                    }
-                } else if k2_detected {
-                    if let Some(level_name) = level_name {
-                        if text == *level_name {
-                            level_detected = true
-                        }
-                    } else {
-                        level_detected = true
-                    }
-                    k2_detected = false
-                }
-                if level_detected && text == "k4" {
-                    k4_detected = true
-                } else if k4_detected {
-                    level_string = text;
-                    break;
-                }
+impl SaveFormat {
+    /// Guesses the format of a save file from its first bytes
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        let start = data.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(data.len());
+        if data[start..].starts_with(b"<?xml") || data[start..].starts_with(b"<plist") {
+            return Some(SaveFormat::Plain);
+        }
+        // Gzip data always starts with "H4sI" in base64
+        if data.len() >= 4 && xor(data[..4].to_vec(), 11) == b"H4sI" {
+            return Some(SaveFormat::Windows);
+        }
+        if data.len() >= 16 && data.len().is_multiple_of(16) {
+            let cipher = Aes256::new(GenericArray::from_slice(MAC_KEY));
+            let mut block = GenericArray::clone_from_slice(&data[..16]);
+            cipher.decrypt_block(&mut block);
+            if block.starts_with(b"<?xml") || block.starts_with(b"<plist") {
+                return Some(SaveFormat::Mac);
             }
-
-            Ok(Event::Eof) => break, // exits the loop when reaching end of file
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            _ => (), // There are several other `Event`s we do not consider here
         }
-
-        // if we don't keep a borrow elsewhere, we can clear the buffer to keep memory usage low
-        buf.clear();
+        None
     }
-    buf.clear();
-    if level_detected && !k4_detected {
-        return Err(
-            "Level is not initialized! Please open the level, place some objects, then save and quit to initialize the level."
-            .to_string()
-        );
-    } else if !k4_detected {
-        if let Some(level_name) = level_name {
-            return Err(format!("Level named \"{}\" was not found!", level_name));
-        } else {
-            return Err(
-                "No level found! Please create a level for SPWN to operate on!".to_string(),
-            );
+}
+
+/// Whether a file holds a plain decompressed level string rather than a save, which is read
+/// and written as it is
+pub fn is_plain_level_string(data: &[u8]) -> bool {
+    let start = data.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(data.len());
+    // Level headers start with the color channels or the first level settings
+    data[start..].starts_with(b"kS38,") || data[start..].starts_with(b"kA")
+}
+/// Decrypts a save file into its plist XML
+pub fn decrypt_savefile(sf: Vec<u8>, format: SaveFormat) -> Result<Vec<u8>, GdError> {
+    match format {
+        SaveFormat::Mac => {
+            if sf.is_empty() || !sf.len().is_multiple_of(16) {
+                return Err(GdError::Decrypt("length is not a multiple of the AES block size".to_string()));
+            }
+            let cipher = Aes256::new(GenericArray::from_slice(MAC_KEY));
+            let mut data = sf;
+            for block in data.chunks_mut(16) {
+                cipher.decrypt_block(GenericArray::from_mut_slice(block));
+            }
+            let padding = *data.last().unwrap() as usize;
+            if padding == 0 || padding > 16 || data[data.len() - padding..].iter().any(|b| *b as usize != padding) {
+                return Err(GdError::Decrypt("invalid padding".to_string()));
+            }
+            data.truncate(data.len() - padding);
+            Ok(data)
+        },
+        SaveFormat::Plain => Ok(sf),
+        SaveFormat::Windows => {
+            let xor = xor(sf, 11);
+            let replaced = String::from_utf8_lossy(&xor)
+                .replace('-', "+")
+                .replace('_', "/")
+                .replace('\0', "");
+            let b64 = base64::decode(replaced.as_str())?;
+            let mut decoder = gzip::Decoder::new(&b64[..]).map_err(GdError::Gzip)?;
+            let mut data = Vec::new();
+            decoder.read_to_end(&mut data).map_err(GdError::Gzip)?;
+            Ok(data)
         }
     }
+}
+/// Encrypts plist XML into a save file
+pub fn encrypt_savefile(bytes: &[u8], format: SaveFormat) -> Result<Vec<u8>, GdError> {
+    use std::io::Write;
 
-    /*let mut k4_detected = false;
-    for token in xmlparser::Tokenizer::from(String::from_utf8(buf).unwrap().as_str()) {
-        if let xmlparser::Token::Text { text } = token.unwrap() {
-            if k4_detected {
-                level_string = text.as_str().to_string();
-                break;
-            }
-            if text.as_str() == "k4" {
-                k4_detected = true;
+    match format {
+        SaveFormat::Mac => {
+            let cipher = Aes256::new(GenericArray::from_slice(MAC_KEY));
+            let padding = 16 - bytes.len() % 16;
+            let mut data = bytes.to_vec();
+            data.extend(std::iter::repeat_n(padding as u8, padding));
+            for block in data.chunks_mut(16) {
+                cipher.encrypt_block(GenericArray::from_mut_slice(block));
             }
+            Ok(data)
+        },
+        SaveFormat::Plain => Ok(bytes.to_vec()),
+        SaveFormat::Windows => {
+            let mut encoder = zlib::Encoder::new(Vec::new()).map_err(GdError::Gzip)?;
+            encoder.write_all(bytes).map_err(GdError::Gzip)?;
+            let compressed = encoder.finish().into_result().map_err(GdError::Gzip)?;
+            use crc32fast::Hasher;
+
+            let mut hasher = Hasher::new();
+            hasher.update(bytes);
+            let checksum = hasher.finalize();
+
+            let data_size = bytes.len() as u32;
+
+            let mut with_signature = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x0b".to_vec();
+            with_signature.extend(&compressed[2..compressed.len() - 4]);
+            with_signature.extend(checksum.to_le_bytes().to_vec());
+            with_signature.extend(data_size.to_le_bytes().to_vec());
+
+            let encoded = base64::encode(&with_signature)
+                .replace('+', "-")
+                .replace('/', "_")
+                .as_bytes()
+                .to_vec();
+
+            Ok(xor(encoded, 11))
         }
-    }*/
-    //decrypting level string
+    }
+}
+/// Decompresses a level string stored under the `k4` key
+pub fn decode_level_string(encoded: &str) -> Result<String, GdError> {
     let ls_b64 = base_64_decrypt(
-        level_string
+        encoded
             .replace('-', "+")
             .replace('_', "/")
             .replace('\0', "")
             .as_bytes()
             .to_vec(),
-    );
-
-    //println!("{}", String::from_utf8(ls_b64.clone()).unwrap());
+    )?;
 
-    let mut ls_decoder = gzip::Decoder::new(&ls_b64[..]).unwrap();
+    let mut ls_decoder = gzip::Decoder::new(&ls_b64[..]).map_err(GdError::Gzip)?;
     let mut ls_buf = Vec::new();
-    ls_decoder.read_to_end(&mut ls_buf).unwrap();
+    ls_decoder.read_to_end(&mut ls_buf).map_err(GdError::Gzip)?;
 
-    Ok(String::from_utf8(ls_buf).unwrap())
+    Ok(String::from_utf8(ls_buf)?)
 }
+/// Compresses a level string into the form stored under the `k4` key
+pub fn encode_level_string(ls: &str) -> Result<String, GdError> {
+    use std::io::Write;
 
-use quick_xml::Writer;
-use std::fs;
-use std::io::Cursor;
-use std::path::PathBuf;
+    let mut ls_encoder = gzip::Encoder::new(Vec::new()).map_err(GdError::Gzip)?;
+    ls_encoder.write_all(ls.as_bytes()).map_err(GdError::Gzip)?;
+    let b64_encrypted =
+        base64::encode(ls_encoder.finish().into_result().map_err(GdError::Gzip)?);
+    let fin = b64_encrypted.replace('+', "-").replace('/', "_");
+    Ok("H4sIAAAAAAAAC".to_string() + &fin[13..])
+}
+/// A level in a save file
+#[derive(Clone, Debug)]
+pub struct LevelInfo {
+    /// The position of the level in the save file, starting at 0
+    pub index: usize,
+    /// The level's name (`k2`)
+    pub name: String,
+    /// The level's revision (`k46`)
+    pub revision: Option<i32>,
+    /// The number of objects in the level, `None` if it isn't initialized
+    pub object_count: Option<usize>,
+    /// The level's length category (`k23`)
+    pub length: Option<i32>,
+    /// Whether the level has a level string (`k4`)
+    pub initialized: bool,
+    /// Why the level string couldn't be decoded, in which case `object_count` is `None`
+    pub unreadable: Option<String>
+}
+impl LevelInfo {
+    /// The name of the length category as shown in game
+    pub fn length_name(&self) -> &'static str {
+        match self.length {
+            None | Some(0) => "Tiny",
+            Some(1) => "Short",
+            Some(2) => "Medium",
+            Some(3) => "Long",
+            Some(4) => "XL",
+            Some(5) => "Platformer",
+            Some(_) => "Unknown"
+        }
+    }
+}
 
-pub fn encrypt_level_string(
-    ls: String,
-    old_ls: String,
-    path: PathBuf,
-    level_name: Option<String>,
-) -> Result<(), String> {
-    let mut file = fs::File::open(path.clone()).unwrap();
-    let mut file_content = Vec::new();
-    file.read_to_end(&mut file_content).unwrap();
+/// Which level of a save file to operate on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LevelSelector {
+    /// The only level with this name, it's an error if several levels share it
+    Name(String),
+    /// The level at this position (starting at 0) among the levels with this name
+    NameIndex(String, usize),
+    /// The level with this name and revision (`k46`)
+    NameRevision(String, i32),
+    /// The level at this position in the save file, as shown by `list_levels`
+    Position(usize)
+}
+impl fmt::Display for LevelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelSelector::Name(name) => write!(f, "named \"{name}\""),
+            LevelSelector::NameIndex(name, index) => write!(f, "named \"{name}\" with index {index}"),
+            LevelSelector::NameRevision(name, revision) => write!(f, "named \"{name}\" with revision {revision}"),
+            LevelSelector::Position(position) => write!(f, "at position {position}")
+        }
+    }
+}
 
-    //decrypting the savefile
-    let content = decrypt_savefile(file_content)?;
-    let string_content = String::from_utf8_lossy(&content);
+/// A dictionary in the save file's XML that holds a level
+struct LevelDict {
+    /// The direct children of the dictionary as key, value and the byte range of the value
+    entries: Vec<(String, String, Range<usize>)>,
+    /// The byte range of the whole dictionary element
+    range: Range<usize>
+}
+impl LevelDict {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(k, _, _)| k == key).map(|(_, v, _)| v.as_str())
+    }
+    fn range(&self, key: &str) -> Option<Range<usize>> {
+        self.entries.iter().find(|(k, _, _)| k == key).map(|(_, _, r)| r.clone())
+    }
+    fn name(&self) -> &str {
+        self.get("k2").unwrap_or_default()
+    }
+    fn revision(&self) -> Option<i32> {
+        self.get("k46").and_then(|v| v.parse().ok())
+    }
+}
+struct DictFrame {
+    start: usize,
+    entries: Vec<(String, String, Range<usize>)>,
+    key: Option<String>
+}
 
-    let mut reader = Reader::from_str(&string_content);
+/// Finds every dictionary that holds a level (has a `k2` key) in the order they appear.
+/// Works on both abbreviated (`<k>`, `<s>`, `<d>`) and full plist tags
+fn level_dicts(xml: &str) -> Result<Vec<LevelDict>, GdError> {
+    let mut reader = Reader::from_str(xml);
     reader.trim_text(true);
-
-    let mut writer = Writer::new(Cursor::new(Vec::new()));
-
     let mut buf = Vec::new();
 
-    let mut k4_detected = false;
-    let mut done = false;
-    let mut k2_detected = false;
-    let mut level_detected = false;
-
-    //println!("{}", old_ls);
-
-    let full_ls = old_ls + &ls;
+    let mut levels = Vec::new();
+    let mut stack: Vec<DictFrame> = Vec::new();
+    let mut text = String::new();
+    let mut value_start = 0;
 
     loop {
-        match reader.read_event(&mut buf) {
-            // unescape and decode the text event using the reader encoding
-            Ok(Event::Text(e)) => {
-                let text = e.unescape_and_decode(&reader).unwrap();
-                if k4_detected && level_detected {
-                    let encrypted_ls: String = {
-                        let mut ls_encoder = gzip::Encoder::new(Vec::new()).unwrap();
-                        ls_encoder.write_all(full_ls.as_bytes()).unwrap();
-                        let b64_encrypted =
-                            base64::encode(&ls_encoder.finish().into_result().unwrap());
-                        let fin = b64_encrypted.replace('+', "-").replace('/', "_");
-                        "H4sIAAAAAAAAC".to_string() + &fin[13..]
-                    };
-
-                    assert!(writer
-                        .write_event(Event::Text(BytesText::from_plain_str(&encrypted_ls)))
-                        .is_ok());
-                    done = true;
-                    k4_detected = false;
-                } else {
-                    if k4_detected {
-                        k4_detected = false;
+        let position = reader.buffer_position();
+        match reader.read_event(&mut buf)? {
+            Event::Start(e) => match e.name() {
+                b"d" | b"dict" => {
+                    if let Some(frame) = stack.last_mut() {
+                        frame.key = None;
                     }
-                    assert!(writer.write_event(Event::Text(e)).is_ok());
-
-                    if k2_detected {
-                        if let Some(level_name) = &level_name {
-                            if level_name == &text {
-                                level_detected = true;
-                                println!("Writing to level: {}", text);
-                            }
-                        } else {
-                            level_detected = true;
-                            println!("Writing to level: {}", text);
+                    // Skip any whitespace before the tag
+                    let start = position + xml[position..].find('<').unwrap_or(0);
+                    stack.push(DictFrame {
+                        start,
+                        entries: Vec::new(),
+                        key: None
+                    });
+                },
+                _ => {
+                    text.clear();
+                    value_start = reader.buffer_position();
+                }
+            },
+            Event::Text(e) => text = e.unescape_and_decode(&reader)?,
+            Event::End(e) => match e.name() {
+                b"d" | b"dict" => {
+                    let frame = stack.pop().unwrap();
+                    if frame.entries.iter().any(|(k, _, _)| k == "k2") {
+                        levels.push(LevelDict {
+                            entries: frame.entries,
+                            range: frame.start..reader.buffer_position()
+                        });
+                    }
+                },
+                b"k" | b"key" => {
+                    if let Some(frame) = stack.last_mut() {
+                        frame.key = Some(text.clone());
+                    }
+                },
+                name => {
+                    // The value ends where the closing tag `</name>` starts
+                    let value_end = reader.buffer_position() - name.len() - 3;
+                    if let Some(frame) = stack.last_mut() {
+                        if let Some(key) = frame.key.take() {
+                            frame.entries.push((key, text.clone(), value_start..value_end));
                         }
-
-                        k2_detected = false;
                     }
                 }
-
-                if !done && text == "k4" {
-                    k4_detected = true
-                }
-
-                if !done && text == "k2" {
-                    k2_detected = true
+            },
+            Event::Empty(e) => {
+                if let Some(frame) = stack.last_mut() {
+                    if let Some(key) = frame.key.take() {
+                        let value = match e.name() {
+                            b"t" | b"true" => "1",
+                            _ => ""
+                        };
+                        let end = reader.buffer_position();
+                        frame.entries.push((key, value.to_string(), end..end));
+                    }
                 }
-            }
-            Ok(Event::Eof) => break, // exits the loop when reaching end of file
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            Ok(e) => assert!(writer.write_event(e).is_ok()),
+            },
+            Event::Eof => break,
+            _ => ()
         }
-
-        // if we don't keep a borrow elsewhere, we can clear the buffer to keep memory usage low
         buf.clear();
     }
-    let bytes = writer.into_inner().into_inner();
-    //encrypt level save
-    use std::io::Write;
+    Ok(levels)
+}
+/// Finds the position of the selected level among the level dictionaries
+fn select_level(levels: &[LevelDict], selector: &LevelSelector) -> Result<usize, GdError> {
+    let named = |name: &String| levels.iter()
+        .enumerate()
+        .filter(|(_, l)| l.name() == name)
+        .map(|(i, _)| i)
+        .collect::<Vec<usize>>();
+    let found = match selector {
+        LevelSelector::Name(name) => {
+            let candidates = named(name);
+            if candidates.len() > 1 {
+                return Err(GdError::AmbiguousLevel {
+                    name: name.clone(),
+                    candidates: candidates.iter().map(|i| (*i, levels[*i].revision())).collect()
+                });
+            }
+            candidates.first().copied()
+        },
+        LevelSelector::NameIndex(name, index) => named(name).get(*index).copied(),
+        LevelSelector::NameRevision(name, revision) => named(name).into_iter()
+            .find(|i| levels[*i].revision() == Some(*revision)),
+        LevelSelector::Position(position) => Some(*position).filter(|p| *p < levels.len())
+    };
+    found.ok_or_else(|| GdError::LevelNotFound(selector.clone()))
+}
+/// Lists every level in a save file
+pub fn list_levels(sf: Vec<u8>, format: SaveFormat) -> Result<Vec<LevelInfo>, GdError> {
+    let content = decrypt_savefile(sf, format)?;
+    let xml = String::from_utf8_lossy(&content);
+    let mut levels = Vec::new();
+    for (index, dict) in level_dicts(&xml)?.into_iter().enumerate() {
+        // A level that can't be decoded is still listed, so the rest of the save can be seen
+        let decoded = dict.get("k4").map(decode_level_string);
+        let unreadable = match &decoded {
+            Some(Err(e)) => Some(e.to_string()),
+            _ => None
+        };
+        let ls = decoded.and_then(Result::ok);
+        levels.push(LevelInfo {
+            index,
+            name: dict.name().to_string(),
+            revision: dict.revision(),
+            object_count: ls.as_ref().map(|ls| ls.split(';').skip(1).filter(|s| !s.is_empty()).count()),
+            length: dict.get("k23").and_then(|v| v.parse().ok()),
+            initialized: dict.get("k4").is_some(),
+            unreadable
+        });
+    }
+    Ok(levels)
+}
+/// Decrypts a save file and returns the decompressed level string of the selected level
+pub fn get_level_string(ls: Vec<u8>, selector: &LevelSelector, format: SaveFormat) -> Result<String, GdError> {
+    //decrypting the savefile
+    let content = decrypt_savefile(ls, format)?;
+    let xml = String::from_utf8_lossy(&content);
+    let levels = level_dicts(&xml)?;
+    let level = &levels[select_level(&levels, selector)?];
+    let level_string = level.get("k4")
+        .ok_or_else(|| GdError::LevelUninitialized(level.name().to_string()))?;
 
-    if cfg!(target_os = "macos") {
-        panic!("No mac support");
-    } else {
-        let mut encoder = zlib::Encoder::new(Vec::new()).unwrap();
-        encoder.write_all(&bytes).unwrap();
-        let compressed = encoder.finish().into_result().unwrap();
-        use crc32fast::Hasher;
+    //decrypting level string
+    decode_level_string(level_string)
+}
 
-        let mut hasher = Hasher::new();
-        hasher.update(&bytes);
-        let checksum = hasher.finalize();
+use std::fs;
+use std::path::PathBuf;
 
-        let data_size = bytes.len() as u32;
+/// Replaces the level string of the selected level in the save file at `path` with
+/// `old_ls + ls` and re-encrypts the save. Everything else in the save is left byte for byte
+pub fn encrypt_level_string(
+    ls: String,
+    old_ls: String,
+    path: PathBuf,
+    selector: &LevelSelector,
+    format: SaveFormat,
+) -> Result<(), GdError> {
+    let file_content = fs::read(&path)?;
 
-        let mut with_signature = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x0b".to_vec();
-        with_signature.extend(&compressed[2..compressed.len() - 4]);
-        with_signature.extend(checksum.to_le_bytes().to_vec());
-        with_signature.extend(data_size.to_le_bytes().to_vec());
+    //decrypting the savefile
+    let content = decrypt_savefile(file_content, format)?;
+    let mut xml = String::from_utf8_lossy(&content).into_owned();
+    let levels = level_dicts(&xml)?;
+    let level = &levels[select_level(&levels, selector)?];
+    let range = level.range("k4")
+        .ok_or_else(|| GdError::LevelUninitialized(level.name().to_string()))?;
 
-        let encoded = base64::encode(&with_signature)
-            .replace('+', "-")
-            .replace('/', "_")
-            .as_bytes()
-            .to_vec();
+    let full_ls = old_ls + &ls;
+    xml.replace_range(range, &encode_level_string(&full_ls)?);
 
-        let fin = xor(encoded, 11);
-        assert!(fs::write(path, fin).is_ok());
-    }
+    //encrypt level save
+    fs::write(path, encrypt_savefile(xml.as_bytes(), format)?)?;
     Ok(())
-}
\ No newline at end of file
+}
+/// Reads the level string out of a `.gmd` file
+pub fn read_gmd(gmd: Vec<u8>) -> Result<String, GdError> {
+    get_level_string(gmd, &LevelSelector::Position(0), SaveFormat::Plain)
+}
+/// Builds a `.gmd` file holding a level string that isn't in a save, named `name`
+pub fn gmd_from_level_string(name: &str, ls: &str) -> Result<String, GdError> {
+    Ok(format!(
+        r#"<?xml version="1.0"?><plist version="1.0" gjver="2.0"><dict><k>kCEK</k><i>4</i><k>k2</k><s>{0}</s><k>k4</k><s>{1}</s></dict></plist>"#,
+        String::from_utf8_lossy(&quick_xml::escape::escape(name.as_bytes())),
+        encode_level_string(ls)?
+    ))
+}
+/// Builds a `.gmd` file from the selected level with its level string replaced by `ls`
+pub fn export_gmd(sf: Vec<u8>, selector: &LevelSelector, format: SaveFormat, ls: &str) -> Result<String, GdError> {
+    let content = decrypt_savefile(sf, format)?;
+    let xml = String::from_utf8_lossy(&content);
+    let levels = level_dicts(&xml)?;
+    let level = &levels[select_level(&levels, selector)?];
+
+    let encoded = encode_level_string(ls)?;
+    let mut dict = xml[level.range.clone()].to_string();
+    if let Some(range) = level.range("k4") {
+        let start = level.range.start;
+        dict.replace_range(range.start - start..range.end - start, &encoded);
+    }
+    else {
+        // Match the dictionary's own tag style
+        let entry = if dict.starts_with("<dict") {
+            format!("<key>k4</key><string>{encoded}</string>")
+        }
+        else {
+            format!("<k>k4</k><s>{encoded}</s>")
+        };
+        let end = dict.rfind("</").unwrap_or(dict.len());
+        dict.insert_str(end, &entry);
+    }
+    // The level dictionary is nested in a save, but is the root of a .gmd
+    let open = dict.find('>').map(|i| i + 1).unwrap_or(0);
+    let close = dict.rfind("</").unwrap_or(dict.len());
+    Ok(format!(
+        r#"<?xml version="1.0"?><plist version="1.0" gjver="2.0"><dict>{0}</dict></plist>"#,
+        &dict[open..close]
+    ))
+}
@@ -0,0 +1,46 @@
+//! A library for simulating objects in Geometry Dash levels.
+//!
+//! The usual flow is to read `CCLocalLevels.dat`, pull a level string out of it with
+//! [`levelstring::get_level_string`], parse it into a [`Level`], run [`physics::simulate`]
+//! for each [`LayerConfig`] and then write the result back with
+//! [`levelstring::encrypt_level_string`].
+//!
+//! ```no_run
+//! use gdphysics::{Config, Level, levelstring, physics, speed::SpeedTracker};
+//!
+//! let config = Config::new("config.json".to_string()).unwrap();
+//! let data = std::fs::read(&config.path).unwrap();
+//! let format = levelstring::SaveFormat::detect(&data).unwrap();
+//! let selector = config.level_selector();
+//! let ls = levelstring::get_level_string(data, &selector, format).unwrap();
+//! let mut level = Level::try_from(ls).unwrap();
+//! let materials = config.material_library();
+//! for layer in config.simulations {
+//!     let start_speed = layer.start_speed.unwrap_or_else(|| level.header.speed());
+//!     let tracker = SpeedTracker::new(&level.objects, start_speed);
+//!     physics::simulate(&mut level.objects, layer, &tracker, &materials);
+//! }
+//! levelstring::encrypt_level_string(
+//!     level.as_str(),
+//!     String::new(),
+//!     config.path.into(),
+//!     &selector,
+//!     format
+//! ).unwrap();
+//! ```
+pub mod cli;
+pub mod config;
+pub mod error;
+pub mod generated;
+pub mod header;
+pub mod level;
+pub mod levelstring;
+pub mod object;
+pub mod physics;
+pub mod speed;
+
+pub use config::{Config, LayerConfig, ObjectConfig};
+pub use error::GdError;
+pub use header::LevelHeader;
+pub use level::Level;
+pub use object::Obj;
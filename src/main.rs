@@ -1,16 +1,6 @@
-mod levelstring;
-mod object;
-mod config;
-mod speed;
-use object::*;
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
-// https://github.com/Spu7Nix/SPWN-language/blob/master/levelstring/src/lib.rs
-use levelstring::*;
-
-use crate::config::Config;
-mod physics;
+use std::{path::{Path, PathBuf}, process::ExitCode};
+use serde_json::{Map, Value};
+use gdphysics::{Config, LayerConfig, Level, cli::{self, Args, Command}, config::{is_gmd, is_level_string}, generated, levelstring::*, physics, speed};
 // TODO
 // Clean up main - done
 // Account for speed portals - almost done
@@ -23,93 +13,248 @@ mod physics;
 // Add help message - done
 // Add documentation - done
 // Add more random parameters - done
-fn main() {    
-    let args: Vec<String> = std::env::args().collect();    
-    if args.len() == 1 {
-        println!("Usage instructions can be found at https://github.com/Salmon-e/gdphysics"); 
-        return
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}, run \"gdphysics --help\" for usage");
+            return ExitCode::from(2)
+        }
+    };
+    if args.command == Command::Help {
+        print!("{}", cli::help());
+        return ExitCode::SUCCESS
+    }
+    match run(&args) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+fn run(args: &Args) -> Result<(), String> {
+    let config_path = args.config_path.clone().unwrap_or_default();
+    let mut config = Config::load(config_path).map_err(|e| format!("Failed to load config file: {e}"))?;
+    args.apply(&mut config).map_err(|e| format!("Invalid arguments: {e}"))?;
+    if args.command == Command::Restore {
+        if args.dry_run {
+            println!("Dry run, {0} would be restored from {1}", config.path, config.backup_path);
+            return Ok(())
+        }
+        std::fs::copy(&config.backup_path, &config.path).map_err(|e| format!("Failed to restore backup: {e}"))?;
+        println!("Backup restored");
+        return Ok(())
     }
-    let config_path = args.get(1).unwrap();
-    let result = Config::new(config_path.clone());
-    let config = if let Ok(config) = result {
-        config
+    let data = std::fs::read(&config.path).map_err(|e| format!("Failed to load save file: {e}"))?;
+    let (source, ls) = if is_level_string(&config.path) || is_plain_level_string(&data) {
+        let ls = String::from_utf8(data).map_err(|e| format!("Failed to read the level string: {e}"))?;
+        (Source::LevelString, ls.trim_end().to_string())
     }
     else {
-        println!("Failed to load config file: {0}", result.unwrap_err());
-        return
+        let format = config.save_format.or_else(|| SaveFormat::detect(&data))
+            .ok_or("Couldn't detect the format of the save file, set \"save_format\" in the config")?;
+        if args.command == Command::List {
+            let levels = list_levels(data, format).map_err(|e| format!("Failed to read the save file: {e}"))?;
+            print_levels(&levels);
+            return Ok(())
+        }
+        config.check().map_err(|e| format!("Failed to load config file: {e}"))?;
+        let selector = config.level_selector();
+        let ls = get_level_string(data.clone(), &selector, format)
+            .map_err(|e| format!("The save file failed to decrypt: {e}"))?;
+        (Source::Save { data, format, selector }, ls)
     };
-    if args.len() == 3 && args[2] == "restore"{
-        let result = std::fs::copy(config.backup_path.clone(), config.path.clone());
-        if result.is_ok() {
-            println!("Backup restored");
+    let mut level = Level::try_from(ls).map_err(|e| format!("Failed to parse the level: {e}"))?;
+    let name = source.name(&config.path);
+    match args.command {
+        Command::List => {
+            println!("{0} is a plain level string with {1} objects", config.path, level.objects.len());
+            return Ok(())
         }
-        else {
-            println!("Failed to restore backup: {0}", result.unwrap_err())
+        Command::Inspect => {
+            print_inspect(&level, &name, &config);
+            return Ok(())
+        }
+        Command::Export => {
+            let path = args.export_path.as_ref().or(config.output_path.as_ref())
+                .ok_or("Set the file to export to, or \"output_path\" in the config")?;
+            if args.dry_run {
+                println!("Dry run, {0} would be exported to {1}", name, path);
+                return Ok(())
+            }
+            return write_output(&level.as_str(), path, &source, &config.path)
+        }
+        Command::Clean => {
+            // Cleaning removes the generated objects without simulating again
+            let removed = clean_legacy(&mut level, &config.simulations) + generated::clean(&mut level.objects, None);
+            println!("Removed {removed} generated objects");
+        }
+        _ => {
+            let removed = clean_legacy(&mut level, &config.simulations);
+            if removed > 0 {
+                println!("Removed {removed} triggers generated by an older version");
+            }
+            let materials = config.material_library();
+            simulate(&mut level, config.simulations, &materials)?
         }
-        return
     }
-    let result = File::open(config.path.clone());
-    let mut save = if let Ok(save) = result {
-        save
+    if args.dry_run {
+        println!("Dry run, the level would have {0} objects and nothing was written", level.objects.len());
+        return Ok(())
+    }
+    println!("Writing {0} objects", level.objects.len());
+    let ls = level.as_str();
+    let output_path = if let Some(output_path) = &config.output_path {
+        if is_gmd(output_path) || is_level_string(output_path) {
+            return write_output(&ls, output_path, &source, &config.path)
+        }
+        if matches!(source, Source::Save { .. }) {
+            std::fs::copy(&config.path, output_path).map_err(|e| format!("Failed to create output file: {e}"))?;
+        }
+        output_path.clone()
     }
     else {
-        println!("Failed to load save file: {0}", result.unwrap_err());
-        return
+        std::fs::copy(&config.path, &config.backup_path).map_err(|e| format!("Failed to make backup: {e}"))?;
+        println!("Created backup at {0}", config.backup_path);
+        config.path.clone()
     };
-    let mut data = Vec::new();
-    let level_name = config.level_name;
-    if let Err(e) = save.read_to_end(&mut data) {
-        println!("Something went wrong while reading the save file: {0}", e)
-    }
-    let level = get_level_string(data, Some(&level_name));
-    
-    if let Ok(old_ls) = level {       
-        let mut object_strings: Vec<String> = old_ls.split(';').map(|s|s.to_string()).collect();
-        let header = object_strings[0].clone();
-        // Remove header
-        object_strings.remove(0);
-        // Remove trailing semi-colon
-        object_strings.pop();
-        let mut objects: Vec<Obj> = object_strings.iter().map(|s|Obj::from(s.clone())).collect();    
-        let tracker = speed::SpeedTracker::new(&objects);
-        for layer in config.simulations {
-            physics::simulate(&mut objects, layer, &tracker)
-        }
-        println!("Writing {0} objects", objects.len());
-        
-        let mut ls = header + ";";        
-
-        for obj in objects.iter() {         
-            let obj_str = obj.as_str() + ";";            
-            ls.push_str(&obj_str);
-        }
-        let path = PathBuf::from(config.path.clone());
-        let write = true;
-        let overwrite = true;
-        if write {
-            let result = std::fs::copy(config.path.clone(), config.backup_path.clone());
-            if result.is_ok() {
-                println!("Created backup at {0}", config.backup_path);
-                if let Err(e) = encrypt_level_string(
-                    ls,
-                    if overwrite {
-                        String::new()
-                    }
-                    else {
-                        old_ls
-                    }, 
-                    path, 
-                    Some(level_name)
-                ) {
-                    println!("Failed to write to save: {0}", e)
-                }
-            }
-            else {
-                println!("Failed to make backup: {0}", result.unwrap_err())
-            }
+    println!("Writing to {name}");
+    match source {
+        Source::Save { format, selector, .. } => encrypt_level_string(
+            ls,
+            String::new(),
+            PathBuf::from(output_path),
+            &selector,
+            format
+        ).map_err(|e| format!("Failed to write to save: {e}")),
+        Source::LevelString => std::fs::write(output_path, ls).map_err(|e| format!("Failed to write level string: {e}"))
+    }
+}
+/// Where the level is read from and written back to
+enum Source {
+    /// A level in a save file or `.gmd`
+    Save { data: Vec<u8>, format: SaveFormat, selector: LevelSelector },
+    /// A plain level string file, which is read and written as it is
+    LevelString
+}
+impl Source {
+    /// How the level is described in messages
+    fn name(&self, path: &str) -> String {
+        match self {
+            Source::Save { selector, .. } => format!("level {selector}"),
+            Source::LevelString => format!("level string {path}")
         }
     }
+}
+/// Runs each simulation on the level
+fn simulate(level: &mut Level, simulations: Vec<LayerConfig>, materials: &Map<String, Value>) -> Result<(), String> {
+    let start_positions = speed::StartPos::find(&level.objects);
+    for mut layer in simulations {
+        let tracker = speed::SpeedTracker::for_layer(&level.objects, level.header.speed(), &layer)?;
+        // Triggers placed along the level don't work when the player moves freely
+        if level.header.platformer() && layer.output_mode == physics::OutputMode::Position {
+            println!("The level is a platformer, so layer {0} uses the platformer output mode", layer.layer);
+            layer.output_mode = physics::OutputMode::Platformer;
+        }
+        let trigger_layer = layer.trigger_layer();
+        let report = physics::simulate(&mut level.objects, layer, &tracker, materials);
+        println!("{report}");
+        print_start_positions(level, &start_positions, &tracker, trigger_layer);
+    }
+    Ok(())
+}
+/// Removes the untagged triggers older versions generated on every trigger layer, before the
+/// first simulation adds the marker that stops `generated::clean` looking for them
+fn clean_legacy(level: &mut Level, simulations: &[LayerConfig]) -> usize {
+    if generated::generated_group(&level.objects).is_some() {
+        return 0
+    }
+    simulations.iter().map(|s| generated::clean(&mut level.objects, Some(s.trigger_layer()))).sum()
+}
+/// Writes a level string to a .gmd or .txt file
+///
+/// A level that didn't come from a save is named after its file in a .gmd
+fn write_output(ls: &str, path: &str, source: &Source, source_path: &str) -> Result<(), String> {
+    if is_gmd(path) {
+        let gmd = match source {
+            Source::Save { data, format, selector } => export_gmd(data.clone(), selector, *format, ls),
+            Source::LevelString => {
+                let name = Path::new(source_path).file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+                gmd_from_level_string(&name, ls)
+            }
+        };
+        let gmd = gmd.map_err(|e| format!("Failed to export level: {e}"))?;
+        std::fs::write(path, gmd).map_err(|e| format!("Failed to export level: {e}"))?;
+        println!("Exported {0} to {1}", source.name(source_path), path);
+    }
+    else if is_level_string(path) {
+        std::fs::write(path, ls).map_err(|e| format!("Failed to write level string: {e}"))?;
+        println!("Wrote level string to {0}", path);
+    }
     else {
-        println!("The save file failed to decrypt: {0}", level.unwrap_err());
+        return Err(format!("Can only export to a .gmd or .txt file, not {path}"))
+    }
+    Ok(())
+}
+/// Shows the level's settings, the bodies on each simulated layer and the start positions
+fn print_inspect(level: &Level, name: &str, config: &Config) {
+    let header = &level.header;
+    println!("Objects in {name}: {0}", level.objects.len());
+    println!("Speed {0:?}, game mode {1:?}{2}", header.speed(), header.game_mode(), if header.platformer() {", platformer"} else {""});
+    let tracker = speed::SpeedTracker::new(&level.objects, header.speed());
+    for (speed, x) in tracker.speeds.iter().skip(1) {
+        println!("Speed portal at x = {x}: {speed:?}");
+    }
+    for warning in &tracker.warnings {
+        println!("Warning: {warning}");
+    }
+    for (i, start) in speed::StartPos::find(&level.objects).iter().enumerate() {
+        println!("Start position {i} at x = {0} ({1:?})", start.x, start.speed);
+    }
+    let generated_group = generated::generated_group(&level.objects);
+    match generated_group {
+        Some(group) => println!("Generated objects are in group {group}"),
+        None => println!("Nothing has been generated yet")
+    }
+    for layer in &config.simulations {
+        let bodies = physics::find_bodies(&level.objects, layer);
+        println!("Layer {0}: {1} bodies", layer.layer, bodies.len());
+        for (group, body) in bodies {
+            let center = if body.center.is_some() {""} else {", no rotation center"};
+            let text = if body.texts.is_empty() {""} else {", parameters from text"};
+            println!("  Group {group}: {0} points{center}{text}", body.points.len());
+        }
+    }
+}
+/// Reports whether the motion on a layer plays correctly from each start position
+fn print_start_positions(level: &Level, start_positions: &[speed::StartPos], tracker: &speed::SpeedTracker, layer: u16) {
+    let trigger_xs = physics::trigger_xs(&level.objects, layer);
+    for (i, start) in start_positions.iter().enumerate() {
+        let check = tracker.check_start_pos(&level.objects, start, &trigger_xs);
+        println!("Layer {layer}, start position {i} at x = {0} ({1:?}): {check}", start.x, start.speed);
+    }
+}
+fn print_levels(levels: &[LevelInfo]) {
+    for level in levels {
+        let revision = level.revision.map(|r| format!(" (revision {r})")).unwrap_or_default();
+        if let Some(error) = &level.unreadable {
+            println!("{0}: \"{1}\"{2} - unreadable ({3})", level.index, level.name, revision, error);
+        }
+        else if let Some(count) = level.object_count {
+            println!("{0}: \"{1}\"{2} - {3} objects, {4}", level.index, level.name, revision, count, level.length_name());
+        }
+        else {
+            println!("{0}: \"{1}\"{2} - not initialized", level.index, level.name, revision);
+        }
+    }
+    let mut names: Vec<&String> = levels.iter().map(|l| &l.name).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let count = levels.iter().filter(|l| &l.name == name).count();
+        if count > 1 {
+            println!("Warning: {count} levels are named \"{name}\"");
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
 use strum::*;
 use strum_macros::EnumIter;
+use crate::error::GdError;
 use self::AttribKey::*;
 use self::AttribValue::*;
 
@@ -8,6 +8,7 @@ use self::AttribValue::*;
 pub enum AttribType {
     Int, Float, Bool, Array, Hsv, Text 
 }
+/// The value of an object attribute
 #[derive(Clone)]
 pub enum AttribValue {
     Int(i32), Float(f32), Bool(bool), Array(Vec<i32>), 
@@ -49,6 +50,7 @@ impl AttribValue {
         }
     }
 }
+/// Known object attribute keys, numbered by their id in the level string
 #[derive(Hash, PartialEq, Eq, EnumIter, Copy, Clone)]
 pub enum AttribKey {
     ObjID = 1, 
@@ -248,29 +250,86 @@ impl AttribKey {
         }
     }
     pub fn from_id(id: u16) -> Option<Self> {
-        for key in AttribKey::iter() {
-            if key as u16 == id {
-                return Some(key);
-            }
+        AttribKey::iter().find(|key| *key as u16 == id)
+    }
+}
+/// A single attribute of an object
+///
+/// Parsed attributes keep their original `key,value` text, which is written back unchanged
+/// until the attribute is set
+enum Attrib {
+    Known(AttribKey, AttribValue, Option<String>),
+    /// An attribute not covered by `AttribKey`, kept as raw strings so it survives re-serialization
+    Unknown(String, String, Option<String>),
+    /// A key without a value at the end of the object, such as the empty key left by a
+    /// trailing comma
+    Trailing(String)
+}
+impl Attrib {
+    /// The position used when inserting new attributes, keys that aren't numbers go last
+    fn order(&self) -> u16 {
+        match self {
+            Attrib::Known(key, _, _) => *key as u16,
+            Attrib::Unknown(key, _, _) => key.parse().unwrap_or(u16::MAX),
+            Attrib::Trailing(_) => u16::MAX
         }
-        None
     }
 }
 /// A wrapper for a Geometry Dash object
+/// 
+/// Attributes keep the order they were parsed in, new attributes are inserted in key id order
 pub struct Obj {
-    attribs: HashMap<AttribKey, AttribValue>    
+    attribs: Vec<Attrib>
 }
 impl Obj {
+    /// Creates an object with the given object id and position
     pub fn new(id: i32, x: f32, y: f32) -> Self {
         let obj = Obj {
-            attribs: HashMap::new()
+            attribs: Vec::new()
         };        
         obj.with(ObjID, AttribValue::Int(id))
             .with(XPos, Float(x))
             .with(YPos, Float(y))
     }
     pub fn get(&self, key: AttribKey) -> Option<&AttribValue> {
-        self.attribs.get(&key)
+        self.attribs.iter().find_map(|a| match a {
+            Attrib::Known(k, v, _) if *k == key => Some(v),
+            _ => None
+        })
+    }
+    /// Returns the raw value of an attribute that isn't covered by `AttribKey`
+    pub fn get_raw(&self, key: &str) -> Option<&str> {
+        self.attribs.iter().find_map(|a| match a {
+            Attrib::Unknown(k, v, _) if k == key => Some(v.as_str()),
+            _ => None
+        })
+    }
+    /// Sets the raw value of an attribute that isn't covered by `AttribKey`
+    pub fn set_raw(&mut self, key: &str, val: String) {
+        let existing = self.attribs.iter_mut().find_map(|a| match a {
+            Attrib::Unknown(k, v, raw) if k == key => Some((v, raw)),
+            _ => None
+        });
+        if let Some((v, raw)) = existing {
+            *v = val;
+            *raw = None;
+        }
+        else {
+            self.insert(Attrib::Unknown(key.to_string(), val, None));
+        }
+    }
+    /// Returns the text of a text object, which is stored as base64
+    pub fn text(&self) -> Option<String> {
+        if let Some(AttribValue::Text(text)) = self.get(AttribKey::Text) {
+            let bytes = base64::decode_config(text, base64::URL_SAFE).ok()?;
+            String::from_utf8(bytes).ok()
+        }
+        else {
+            None
+        }
+    }
+    pub fn set_text(&mut self, text: &str) {
+        self.set(AttribKey::Text, AttribValue::Text(base64::encode_config(text, base64::URL_SAFE)));
     }
     pub fn get_pos(&self) -> (f32, f32) {
         let x = self.get(XPos);
@@ -286,75 +345,107 @@ impl Obj {
         self.with(Rotation, Float(rot))
     }
     pub fn set(&mut self, key: AttribKey, val: AttribValue) {
-        self.attribs.insert(key, val);
+        let existing = self.attribs.iter_mut().find_map(|a| match a {
+            Attrib::Known(k, v, raw) if *k == key => Some((v, raw)),
+            _ => None
+        });
+        if let Some((v, raw)) = existing {
+            *v = val;
+            *raw = None;
+        }
+        else {
+            self.insert(Attrib::Known(key, val, None));
+        }
     }
     pub fn with(mut self, key: AttribKey, val: AttribValue) -> Self {
         assert!(key.get_type() == val.get_type());
-        self.attribs.insert(key, val);
+        self.set(key, val);
         self
     }
+    /// Inserts a new attribute before the first one with a higher key id, and always before
+    /// a trailing key
+    fn insert(&mut self, attrib: Attrib) {
+        let order = attrib.order();
+        let index = self.attribs.iter()
+            .position(|a| a.order() > order || matches!(a, Attrib::Trailing(_)))
+            .unwrap_or(self.attribs.len());
+        self.attribs.insert(index, attrib);
+    }
+    /// Serializes the object in level string format, without the trailing semicolon
     pub fn as_str(&self) -> String {
-        let mut str = String::new();
-        for (key, value) in self.attribs.iter() {
-            let key_id = *key as u16;
-            let value_str = value.serialize();
-            let pair = format!("{key_id}, {value_str},");
-            str.push_str(&*pair);
+        let mut pairs = Vec::new();
+        for attrib in self.attribs.iter() {
+            match attrib {
+                Attrib::Known(_, _, Some(raw)) | Attrib::Unknown(_, _, Some(raw)) => pairs.push(raw.clone()),
+                Attrib::Known(key, value, None) => {
+                    let key_id = *key as u16;
+                    let value_str = value.serialize();
+                    pairs.push(format!("{key_id},{value_str}"));
+                },
+                Attrib::Unknown(key, value, None) => pairs.push(format!("{key},{value}")),
+                Attrib::Trailing(key) => pairs.push(key.clone())
+            }
         }
-        str 
+        pairs.join(",")
     }
 }
-impl From<String> for Obj {
-    fn from(str: String) -> Self {
+/// Parses an object from its level string form. Errors report the object index as 0,
+/// `Level` fills in the real index when parsing a whole level
+impl TryFrom<String> for Obj {
+    type Error = GdError;
+    fn try_from(str: String) -> Result<Self, GdError> {
         let split: Vec<&str> = str.split(',').collect();
         let mut obj = Obj {
-            attribs: HashMap::new()
+            attribs: Vec::new()
         };
         for i in (0..split.len() - 1).step_by(2) {
             let key_str: String = split[i].chars().filter(|c| !c.is_whitespace()).collect();
             let val_str: String = split[i + 1].chars().filter(|c| !c.is_whitespace()).collect();
-            if let Ok(key_id) = key_str.parse::<u16>() {
-                if let Some(key) = AttribKey::from_id(key_id) {
-                    let value = match key.get_type() {
-                        AttribType::Int => {
-                            let parsed = val_str.parse::<i32>().unwrap_or_else(|_| panic!("Failed to parse integer: {val_str}"));
-                            AttribValue::Int(parsed)
-                        },
-                        AttribType::Float => {
-                            let parsed = val_str.parse::<f32>().unwrap_or_else(|_| panic!("Failed to parse float: {val_str}"));
-                            AttribValue::Float(parsed)
-                        },
-                        AttribType::Bool => {
-                            let val = val_str.parse::<i32>().unwrap_or_else(|_| panic!("Failed to parse bo: {val_str}"));
-                            AttribValue::Bool(val == 1)
-                        },
-                        AttribType::Array => {
-                            let split = val_str.split('.');
-                            let mut arr = Vec::new();
-                            for i in split {
-                                arr.push(i.parse().unwrap_or_else(|_| panic!("Failed to parse array element: {val_str}")))
-                            }
-                            AttribValue::Array(arr)
-                        },
-                        AttribType::Hsv => {
-                            let split: Vec<&str> = val_str.split('a').collect();
-                            assert!(split.len() == 5);
-                            AttribValue::Hsv { 
-                                h: split[0].parse().unwrap_or_else(|_| panic!("Failed to parse hue: {val_str}")), 
-                                s: split[1].parse().unwrap_or_else(|_| panic!("Failed to parse saturation: {val_str}")), 
-                                v: split[2].parse().unwrap_or_else(|_| panic!("Failed to parse value: {val_str}")), 
-                                s_checked: split[3].parse::<u16>().unwrap_or_else(|_| panic!("Failed to parse s_checked: {val_str}")) == 1,
-                                v_checked: split[4].parse::<u16>().unwrap_or_else(|_| panic!("Failed to parse v_checked: {val_str}")) == 1
-                            }
-                        },
-                        AttribType::Text => {
-                            AttribValue::Text(val_str.to_string())
+            let raw = format!("{0},{1}", split[i], split[i + 1]);
+            let key = key_str.parse::<u16>().ok().and_then(AttribKey::from_id);
+            if let Some(key) = key {
+                let err = || GdError::ObjectParse {
+                    index: 0,
+                    key: key as u16,
+                    value: val_str.clone()
+                };
+                let value = match key.get_type() {
+                    AttribType::Int => AttribValue::Int(val_str.parse().map_err(|_| err())?),
+                    AttribType::Float => AttribValue::Float(val_str.parse().map_err(|_| err())?),
+                    AttribType::Bool => AttribValue::Bool(val_str.parse::<i32>().map_err(|_| err())? == 1),
+                    AttribType::Array => {
+                        let mut arr = Vec::new();
+                        for i in val_str.split('.') {
+                            arr.push(i.parse().map_err(|_| err())?)
                         }
-                    };
-                    obj.attribs.insert(key, value);
-                }
+                        AttribValue::Array(arr)
+                    },
+                    AttribType::Hsv => {
+                        let split: Vec<&str> = val_str.split('a').collect();
+                        if split.len() != 5 {
+                            return Err(err());
+                        }
+                        AttribValue::Hsv { 
+                            h: split[0].parse().map_err(|_| err())?, 
+                            s: split[1].parse().map_err(|_| err())?, 
+                            v: split[2].parse().map_err(|_| err())?, 
+                            s_checked: split[3].parse::<u16>().map_err(|_| err())? == 1,
+                            v_checked: split[4].parse::<u16>().map_err(|_| err())? == 1
+                        }
+                    },
+                    AttribType::Text => {
+                        AttribValue::Text(val_str.to_string())
+                    }
+                };
+                obj.attribs.push(Attrib::Known(key, value, Some(raw)));
+            }
+            else {
+                obj.attribs.push(Attrib::Unknown(key_str, split[i + 1].to_string(), Some(raw)));
             }
         }
-        obj
+        if split.len() % 2 == 1 {
+            obj.attribs.push(Attrib::Trailing(split[split.len() - 1].to_string()));
+        }
+        Ok(obj)
     }
-}
\ No newline at end of file
+}
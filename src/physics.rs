@@ -1,72 +1,86 @@
-use crate::{object::{*, AttribKey::*, AttribValue::*}, config::{LayerConfig, ObjectConfig}, speed::SpeedTracker};
+use crate::{object::{*, AttribKey::*, AttribValue::*}, config::{LayerConfig, ObjectConfig}, generated, speed::SpeedTracker};
 use rapier2d::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use serde_json::{Map, Value};
 use std::collections::*;
 
-pub fn simulate(objects: &mut Vec<Obj>, config: LayerConfig, tracker: &SpeedTracker) {
-    let mut shapes: HashMap<i32, Vec<Point<Real>>> = HashMap::new();
-    let mut shape_groups: Vec<i32> = Vec::new();
-    let mut centers: HashMap<i32, ObjCenter> = HashMap::new();
-    let mut used_link_groups = Vec::new();
-    let rotate_trigger = 1346;
-    let move_trigger = 901;
-    let mut i = 0;
+const MOVE_TRIGGER: i32 = 901;
+const ROTATE_TRIGGER: i32 = 1346;
+const SPAWN_TRIGGER: i32 = 1268;
+const TEXT_OBJECT: i32 = 914;
+
+/// How the generated triggers are activated
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Each keyframe's triggers are placed where the player is at that time
+    #[default]
+    Position,
+    /// The keyframes are spawned by a chain of delayed spawn triggers, started when the player
+    /// passes the anchor, so playback doesn't depend on the player's movement
+    Spawn,
+    /// Like `Spawn`, but the chain is started by touching a trigger or by spawning
+    /// `start_group`, since the player doesn't move at a constant rate in platformer levels
+    Platformer
+}
+
+/// Simulates the selected bodies on the configured layer and appends the move and rotate
+/// triggers that play the motion back to the trigger layer, replacing the triggers generated
+/// there by a previous run
+///
+/// `materials` are the materials text objects can name, usually from `Config::material_library`
+pub fn simulate(objects: &mut Vec<Obj>, config: LayerConfig, tracker: &SpeedTracker, materials: &Map<String, Value>) -> SimulationReport {
+    // Ordered maps keep the output stable between runs
+    let mut shapes: BTreeMap<i32, Vec<Point<Real>>> = BTreeMap::new();
+    let mut centers: BTreeMap<i32, ObjCenter> = BTreeMap::new();
+    // Body parameters written in text objects, by group
+    let mut texts: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+    let used_link_groups: Vec<i32> = objects.iter()
+        .filter_map(|o| match o.get(LinkedGroupID) {
+            Some(Int(group)) => Some(*group),
+            _ => None
+        })
+        .collect();
+    // Only the output of a previous run is replaced, triggers made by hand are kept
+    let trigger_layer = config.trigger_layer();
+    let removed = generated::clean(objects, Some(trigger_layer));
+    let mut warnings = tracker.warnings.clone();
+    let generated_group = generated::ensure_generated_group(objects);
     let mut anchor_obj_index = -1;
-    while i < objects.len() {
-        let object = &objects[i];
+    for (i, object) in objects.iter().enumerate() {
         let mut layer = 0;
-        if let Some(Int(group)) = object.get(LinkedGroupID) {
-            used_link_groups.push(*group);
-        }
         if let Some(AttribValue::Int(l)) = object.get(EditorLayer1) {
             layer = *l;
         }
-        if let Some(Int(id)) = object.get(ObjID) {
-            let id = *id;
-            if layer == config.layer as i32 && id == config.anchor_id {
-                anchor_obj_index = i as i32
-            }
-            if layer == config.layer as i32 && (id == move_trigger || id == rotate_trigger) {
-                objects.remove(i);                    
-            }
-            else {
-                i += 1;
-            }
-            
-        }            
+        if layer == config.layer as i32 && matches!(object.get(ObjID), Some(Int(id)) if *id == config.anchor_id) {
+            anchor_obj_index = i as i32
+        }
     }
-    let anchor_x = if anchor_obj_index != -1 {
-        objects[anchor_obj_index as usize].get_pos().0
+    let (anchor_x, anchor_y) = if anchor_obj_index != -1 {
+        objects[anchor_obj_index as usize].get_pos()
     }
     else {
-        0.0
+        warnings.push(format!("No anchor (object id {0}) on layer {1}, the motion starts at x = 0", config.anchor_id, config.layer));
+        (0.0, 0.0)
     };
     
     let link_group = used_link_groups.iter().max().unwrap_or(&0) + 1;
-    for (i, object) in objects.iter().enumerate() {
-        let mut layer = 0;      
-        
-        if let Some(AttribValue::Int(l)) = object.get(EditorLayer1) {
-            layer = *l;
+    for (group, body) in find_bodies(objects, &config) {
+        let points = body.points.iter().map(|i| {
+            let p = objects[*i].get_pos();
+            [p.0, p.1].into()
+        });
+        if !body.points.is_empty() {
+            shapes.insert(group, points.collect());
         }
-        if layer == config.layer as i32 {          
-            if let Some(Array(groups)) = object.get(GroupIDs) {
-                if groups.is_empty() {continue}
-
-                if let Some(points) = shapes.get_mut(&groups[0]) {
-                    let p = object.get_pos();
-                    points.push([p.0, p.1].into())
-                }
-                else {
-                    let p = object.get_pos();
-                    let points = vec![[p.0, p.1].into()];
-                    shapes.insert(groups[0], points);
-                    shape_groups.push(groups[0]);                    
-                }
-                if groups.len() > 1 {
-                    centers.insert(groups[0], ObjCenter { center_group: groups[1], obj: i });
-                }
+        if let Some(center) = body.center {
+            if let Some(Array(groups)) = objects[center].get(GroupIDs) {
+                centers.insert(group, ObjCenter { center_group: groups[1], obj: center });
             }
         }
+        if !body.texts.is_empty() {
+            texts.insert(group, body.texts.iter().map(|i| objects[*i].text().unwrap_or_default()).collect());
+        }
     }
     
     let mut collider_set = ColliderSet::new();
@@ -86,12 +100,24 @@ pub fn simulate(objects: &mut Vec<Obj>, config: LayerConfig, tracker: &SpeedTrac
             *point -= center;
         }
         if points.len() <= 2 {
+            warnings.push(format!("Group {0} has {1} points, at least 3 are needed to make a body", group, points.len()));
             continue
         }
+        if !centers.contains_key(group) {
+            warnings.push(format!("Group {group} has no rotation center, so it won't be moved"));
+        }
         let obj_config_option = config.objects.iter()
             .find(|c| c.group as i32 == *group);
-        let default_config = ObjectConfig::new(*group as u16);
-        let obj_config = if let Some(c) = obj_config_option {c} else {&default_config};
+        let default_config = ObjectConfig::with_defaults(*group as u16, &config.object_defaults);
+        let mut obj_config = if let Some(c) = obj_config_option {c} else {&default_config};
+        let text_config = texts.get(group).map(|text| obj_config.with_text(&text.join("\n"), materials));
+        match &text_config {
+            Some(Ok(c)) => obj_config = c,
+            Some(Err(problems)) => for problem in problems {
+                warnings.push(format!("Group {group} has a text object with an invalid parameter, {problem}"));
+            }
+            None => {}
+        }
         let collider_builder = ColliderBuilder::convex_hull(points).unwrap();
         let rigid_body_builder = if obj_config.dynamic { RigidBodyBuilder::dynamic() } 
             else { RigidBodyBuilder::fixed() }
@@ -117,7 +143,7 @@ pub fn simulate(objects: &mut Vec<Obj>, config: LayerConfig, tracker: &SpeedTrac
     
     let gravity = vector!(config.gravity[0], config.gravity[1]);
     let mut integration_parameters = IntegrationParameters::default();
-    integration_parameters.set_inv_dt(config.fps as f32);
+    integration_parameters.set_inv_dt(config.fps);
     let mut physics_pipeline = PhysicsPipeline::new();
     let mut island_manager = IslandManager::new();
     let mut broad_phase = BroadPhase::new();
@@ -128,9 +154,9 @@ pub fn simulate(objects: &mut Vec<Obj>, config: LayerConfig, tracker: &SpeedTrac
     let physics_hooks = ();
     let event_handler = ();    
 
-    let mut history = HashMap::new();
-    for group in shape_groups {
-        history.insert(group, Vec::new());
+    let mut history = BTreeMap::new();
+    for (group, _) in handles.iter() {
+        history.insert(*group, Vec::new());
     }
     
     for _ in 0..(config.sim_time / (integration_parameters.dt * config.keyframe_interval as f32)) as i32 {        
@@ -162,38 +188,44 @@ pub fn simulate(objects: &mut Vec<Obj>, config: LayerConfig, tracker: &SpeedTrac
         }
     }
     for (group, path) in history.iter() {
-        if let Some(center) = centers.get(group) {
+        if let (Some(center), Some(first)) = (centers.get(group), path.first()) {
             let obj = &mut objects[center.obj];
-            obj.set(XPos, Float(path[0].x));
-            obj.set(YPos, Float(path[0].y));
+            obj.set(XPos, Float(first.x));
+            obj.set(YPos, Float(first.y));
         } 
     }
     
     let mut height = config.height;
     let duration = integration_parameters.dt * config.keyframe_interval as f32;    
+    let start_time = tracker.time_at(anchor_x);
+    // Each trigger with the keyframe it starts
+    let mut triggers: Vec<(usize, Obj)> = Vec::new();
     for (group, path) in history.iter() {
-        let (start_x, start_y, start_rot) = (path[0].x, path[0].y, path[0].rotation);
+        // Simulations shorter than one keyframe have no motion
+        let Some(first) = path.first() else {continue};
+        let (start_x, start_y, start_rot) = (first.x, first.y, first.rotation);
         let center_group = if let Some(g) = centers.get(group) {g.center_group} else {continue};
 
-        let mut trigger_x = anchor_x;
         let (mut total_dx, mut total_dy, mut total_rot) = (start_x, start_y, start_rot);
         
-        for state in path.iter().skip(1) {
+        for (keyframe, state) in path.iter().skip(1).enumerate() {
+            // Place the trigger where the player is when the keyframe starts
+            let trigger_x = tracker.x_at(start_time + duration * keyframe as f32);
             let dx = state.x - total_dx;
             let dy = state.y - total_dy;
             let dr = (state.rotation - total_rot) % 360.0;       
             
             if dx.round().abs() >= 1.0 || dy.round().abs() >= 1.0 {
-                let movement = Obj::new(move_trigger, trigger_x, height)
+                let movement = Obj::new(MOVE_TRIGGER, trigger_x, height)
                     .with(MoveOffsetX, Int(dx.round() as i32))
                     .with(MoveOffsetY, Int(dy.round() as i32))
                     .with(TargetGroupID, Int(*group))
                     .with(Duration, Float(duration))
-                    .with(EditorLayer1, Int(config.layer as i32))
+                    .with(EditorLayer1, Int(trigger_layer as i32))
                     .with(LinkedGroupID, Int(link_group));
                 total_dx += dx.round();
                 total_dy += dy.round();
-                objects.push(movement);                
+                triggers.push((keyframe, movement));                
             }
             if dr.round().abs() >= 1.0 {                
                 let degrees = if dr.round().abs() <= 180.0 {
@@ -208,21 +240,175 @@ pub fn simulate(objects: &mut Vec<Obj>, config: LayerConfig, tracker: &SpeedTrac
                     }
                 }; 
                 total_rot += degrees;               
-                let rotation = Obj::new(rotate_trigger, trigger_x, height + 30.0)
+                let rotation = Obj::new(ROTATE_TRIGGER, trigger_x, height + 30.0)
                     .with(RotateDegrees, Int(degrees as i32))
                     .with(Duration, Float(duration * config.rotation_duration_modifier))
                     .with(TargetGroupID, Int(*group))
                     .with(SecondaryGroupID, Int(center_group))
-                    .with(EditorLayer1, Int(config.layer as i32))
+                    .with(EditorLayer1, Int(trigger_layer as i32))
                     .with(LinkedGroupID, Int(link_group));
                 if degrees as i32 != 0 {
-                    objects.push(rotation);
+                    triggers.push((keyframe, rotation));
                 }
             }
-            trigger_x += tracker.speed_at(trigger_x).bps() * duration * 30.0;
         }
         height -= 60.0;
     }
+    let start = match config.output_mode {
+        OutputMode::Position => None,
+        OutputMode::Spawn => Some(spawn_trigger(anchor_x, config.height + 60.0, trigger_layer, link_group)),
+        OutputMode::Platformer => if let Some(group) = config.start_group {
+            Some(spawn_trigger(anchor_x, config.height + 60.0, trigger_layer, link_group)
+                .with(SpawnTriggered, Bool(true))
+                .with(GroupIDs, Array(vec![group])))
+        }
+        else {
+            let [x, y] = config.start_trigger_pos.unwrap_or([anchor_x, anchor_y]);
+            Some(spawn_trigger(x, y, trigger_layer, link_group)
+                .with(TouchTriggered, Bool(true)))
+        }
+    };
+    let output = if let Some(start) = start {
+        let start_group = config.start_group.unwrap_or(0);
+        let groups = generated::free_groups(objects, start_group);
+        spawn_chain(triggers, start, groups, duration, config.height + 60.0, trigger_layer, link_group)
+    }
+    else {
+        triggers.into_iter().map(|(_, trigger)| trigger).collect()
+    };
+    let x_range = output.iter()
+        .map(|o| o.get_pos().0)
+        .fold(None, |range: Option<(f32, f32)>, x| match range {
+            Some((min, max)) => Some((min.min(x), max.max(x))),
+            None => Some((x, x))
+        });
+    let report = SimulationReport {
+        layer: config.layer,
+        bodies: handles.len(),
+        removed,
+        generated: output.len(),
+        x_range,
+        warnings
+    };
+    objects.extend(output.into_iter().map(|o| generated::tag(o, generated_group)));
+    report
+}
+/// The objects that make up one body, as indices into the level's objects
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BodyObjects {
+    /// The points whose convex hull is the body's shape, including the rotation center
+    pub points: Vec<usize>,
+    /// The point with a second group, which the body rotates around
+    pub center: Option<usize>,
+    /// The text objects holding the body's parameters, if `text_config` is set
+    pub texts: Vec<usize>
+}
+/// Finds the bodies a simulation selects on its layer by their main group, leaving out
+/// generated objects
+pub fn find_bodies(objects: &[Obj], config: &LayerConfig) -> BTreeMap<i32, BodyObjects> {
+    let generated_group = generated::generated_group(objects);
+    let mut bodies: BTreeMap<i32, BodyObjects> = BTreeMap::new();
+    for (i, object) in objects.iter().enumerate() {
+        let layer = match object.get(EditorLayer1) {
+            Some(Int(l)) => *l,
+            _ => 0
+        };
+        let Some(Array(groups)) = object.get(GroupIDs) else {continue};
+        // Triggers generated by another simulation can be on this layer
+        if layer != config.layer as i32 || groups.is_empty() || !config.selects(groups[0])
+            || generated_group.is_some_and(|g| generated::is_generated(object, g)) {
+            continue
+        }
+        let body = bodies.entry(groups[0]).or_default();
+        if config.text_config && matches!(object.get(ObjID), Some(Int(TEXT_OBJECT))) {
+            body.texts.push(i);
+            continue
+        }
+        body.points.push(i);
+        if groups.len() > 1 {
+            body.center = Some(i);
+        }
+    }
+    bodies
+}
+/// What a simulation changed in the level
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationReport {
+    pub layer: u16,
+    /// How many bodies were simulated
+    pub bodies: usize,
+    /// How many objects from a previous run were removed
+    pub removed: usize,
+    /// How many triggers were generated
+    pub generated: usize,
+    /// The lowest and highest x of the generated triggers
+    pub x_range: Option<(f32, f32)>,
+    pub warnings: Vec<String>
+}
+impl std::fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Layer {0}: {1} bodies, {2} triggers removed, {3} triggers generated", self.layer, self.bodies, self.removed, self.generated)?;
+        if let Some((min, max)) = self.x_range {
+            write!(f, " from x = {min} to {max}")?;
+        }
+        for warning in &self.warnings {
+            write!(f, "\n  Warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+fn spawn_trigger(x: f32, y: f32, layer: u16, link_group: i32) -> Obj {
+    Obj::new(SPAWN_TRIGGER, x, y)
+        .with(EditorLayer1, Int(layer as i32))
+        .with(LinkedGroupID, Int(link_group))
+}
+/// Makes the triggers of each keyframe spawn triggered and links them with a chain of spawn
+/// triggers, started by the `start` spawn trigger, so the motion plays back in time rather
+/// than following the player
+///
+/// Each keyframe with triggers gets the next of `groups`
+fn spawn_chain(triggers: Vec<(usize, Obj)>, start: Obj, groups: impl Iterator<Item = i32>, duration: f32, height: f32, layer: u16, link_group: i32) -> Vec<Obj> {
+    let mut keyframes: BTreeMap<usize, Vec<Obj>> = BTreeMap::new();
+    for (keyframe, trigger) in triggers {
+        keyframes.entry(keyframe).or_default().push(trigger);
+    }
+    let mut chain = Vec::new();
+    // The last keyframe in the chain and the spawn trigger that will start the next one
+    let mut previous = (0, start);
+    for (group, (keyframe, triggers)) in groups.zip(keyframes) {
+        let (previous_keyframe, link) = previous;
+        let delay = duration * (keyframe - previous_keyframe) as f32;
+        chain.push(link.with(TargetGroupID, Int(group)).with(SpawnDelay, Float(delay)));
+        let x = triggers[0].get_pos().0;
+        for trigger in triggers {
+            chain.push(trigger
+                .with(SpawnTriggered, Bool(true))
+                .with(GroupIDs, Array(vec![group])));
+        }
+        let next = spawn_trigger(x, height, layer, link_group)
+            .with(SpawnTriggered, Bool(true))
+            .with(GroupIDs, Array(vec![group]));
+        previous = (keyframe, next);
+    }
+    chain
+}
+/// The x positions of the triggers on a layer that `simulate` generates and the player
+/// activates by passing them
+pub fn trigger_xs(objects: &[Obj], layer: u16) -> Vec<f32> {
+    let Some(generated_group) = generated::generated_group(objects) else {
+        return Vec::new()
+    };
+    objects.iter()
+        .filter(|o| generated::is_generated(o, generated_group))
+        .filter(|o| match o.get(EditorLayer1) {
+            Some(Int(l)) => *l == layer as i32,
+            _ => layer == 0
+        })
+        .filter(|o| matches!(o.get(ObjID), Some(Int(MOVE_TRIGGER | ROTATE_TRIGGER | SPAWN_TRIGGER))))
+        .filter(|o| !matches!(o.get(SpawnTriggered), Some(Bool(true))))
+        .filter(|o| !matches!(o.get(TouchTriggered), Some(Bool(true))))
+        .map(|o| o.get_pos().0)
+        .collect()
 }
 struct BodyState {
     x: f32,
@@ -1,37 +1,191 @@
-use crate::object::{*, AttribKey::*, AttribValue::*};
+use crate::{config::LayerConfig, object::{*, AttribKey::*, AttribValue::*}};
 use self::Speed::*;
+use serde_derive::{Serialize, Deserialize};
+/// Portals closer than this along the x axis are touched on the same frame
+const SAME_X: f32 = 1.0;
+/// Start positions whose timing is off by less than this many seconds play correctly
+const DESYNC_TOLERANCE: f32 = 1.0 / 60.0;
+const START_POS_ID: i32 = 31;
+/// Tracks the player's speed along the x axis using the speed portals in a level
 pub struct SpeedTracker {
-    pub speeds: Vec<(Speed, f32)>
+    /// Each speed and the x position it starts at, sorted by x
+    pub speeds: Vec<(Speed, f32)>,
+    /// Problems found with the level's portals, such as conflicting portals at the same x
+    pub warnings: Vec<String>
 }
 impl SpeedTracker {
-    pub fn new(objects: &Vec<Obj>) -> Self {
-        let mut portals: Vec<(Speed, f32)> = vec![(Normal, 0.0)];
+    /// Creates a tracker for a level whose player starts at `start_speed`, usually the
+    /// speed in the level header
+    ///
+    /// Every speed portal changes the speed when the player reaches its left edge. The
+    /// portal's check only affects the editor preview, so it is ignored
+    pub fn new(objects: &Vec<Obj>, start_speed: Speed) -> Self {
+        SpeedTracker::starting_at(objects, start_speed, 0.0)
+    }
+    /// Creates a tracker for a player who starts at `start_x` at `start_speed`, ignoring
+    /// the portals before it
+    pub fn starting_at(objects: &Vec<Obj>, start_speed: Speed, start_x: f32) -> Self {
+        let mut portals: Vec<(Speed, f32)> = Vec::new();
         for object in objects {
-            if let Int(id) = object.get(ObjID).unwrap() {
+            if let Some(Int(id)) = object.get(ObjID) {
                 if let Some(speed) = Speed::from(*id) {
-                    if let Some(Bool(b)) = object.get(SpecialCheck) {
-                        if *b {
-                            portals.push((speed, object.get_pos().0 - speed.width()/2.0))                            
-                        }
-                    }
+                    portals.push((speed, object.get_pos().0 - speed.width()/2.0))
                 }
             }
         }
-        portals.dedup();
+        portals.retain(|(_, x)| *x > start_x);
+        // A stable sort keeps portals at the same x in level order
+        portals.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let mut warnings = Vec::new();
+        let mut speeds = vec![(start_speed, start_x)];
+        let mut i = 0;
+        while i < portals.len() {
+            let (_, x) = portals[i];
+            let same_x = portals[i..].iter()
+                .take_while(|(_, other)| other - x < SAME_X)
+                .count();
+            let group = &portals[i..i + same_x];
+            // The player touches all of them on the same frame, the last one in the level wins
+            let (speed, _) = group[same_x - 1];
+            if group.iter().any(|(s, _)| *s != speed) {
+                let names: Vec<String> = group.iter().map(|(s, _)| format!("{s:?}")).collect();
+                warnings.push(format!("{0} speed portals overlap at x = {x} ({1}), using {speed:?}", same_x, names.join(", ")));
+            }
+            speeds.push((speed, x));
+            i += same_x;
+        }
+        // Portals that don't change the speed don't start a new segment
+        speeds.dedup_by_key(|(speed, _)| *speed);
         SpeedTracker {
-            speeds: portals
+            speeds,
+            warnings
         }
     }
+    /// Returns the speed the player has at the given x position
     pub fn speed_at(&self, x: f32) -> Speed {
         for (speed, dist) in self.speeds.iter().rev() {        
             if x >= *dist {
                 return *speed
             }    
         }
-        return self.speeds[0].0
+        self.speeds[0].0
+    }
+    /// Returns the time in seconds it takes the player to reach `x` from where they start
+    pub fn time_at(&self, x: f32) -> f32 {
+        let mut time = 0.0;
+        for (i, (speed, start)) in self.speeds.iter().enumerate() {
+            let end = self.speeds.get(i + 1).map(|s| s.1).unwrap_or(f32::INFINITY);
+            // Positions before the start are reached at negative times at the starting speed
+            if x < end || i == 0 && x < *start {
+                return time + (x - start) / speed.units_per_second();
+            }
+            time += (end - start) / speed.units_per_second();
+        }
+        time
+    }
+    /// Returns the x position the player is at `time` seconds after they start
+    pub fn x_at(&self, time: f32) -> f32 {
+        let mut elapsed = 0.0;
+        for (i, (speed, start)) in self.speeds.iter().enumerate() {
+            let end = self.speeds.get(i + 1).map(|s| s.1).unwrap_or(f32::INFINITY);
+            let duration = (end - start) / speed.units_per_second();
+            if time < elapsed + duration || i == 0 && time < 0.0 {
+                return start + (time - elapsed) * speed.units_per_second();
+            }
+            elapsed += duration;
+        }
+        self.speeds[0].1
+    }
+    /// Creates a tracker for a player who starts at a start position
+    pub fn from_start_pos(objects: &Vec<Obj>, start: &StartPos) -> Self {
+        SpeedTracker::starting_at(objects, start.speed, start.x)
+    }
+    /// Creates the tracker a simulation's triggers are timed with, starting from its
+    /// `start_pos` if set and otherwise from the start of the level at `level_speed`,
+    /// with `start_speed` overriding either speed
+    pub fn for_layer(objects: &Vec<Obj>, level_speed: Speed, layer: &LayerConfig) -> Result<Self, String> {
+        match layer.start_pos {
+            Some(i) => {
+                let start_positions = StartPos::find(objects);
+                let start = start_positions.get(i)
+                    .ok_or(format!("Start position {0} doesn't exist, the level has {1}", i, start_positions.len()))?;
+                Ok(SpeedTracker::starting_at(objects, layer.start_speed.unwrap_or(start.speed), start.x))
+            }
+            None => Ok(SpeedTracker::new(objects, layer.start_speed.unwrap_or(level_speed)))
+        }
+    }
+    /// Checks whether motion whose triggers are at `trigger_xs`, placed using this tracker,
+    /// plays back with the same timing from a start position
+    pub fn check_start_pos(&self, objects: &Vec<Obj>, start: &StartPos, trigger_xs: &[f32]) -> StartPosCheck {
+        let Some(first) = trigger_xs.iter().copied().reduce(f32::min) else {
+            return StartPosCheck::Ok
+        };
+        if start.x > first {
+            return StartPosCheck::AfterMotion(first)
+        }
+        let from_start = SpeedTracker::from_start_pos(objects, start);
+        let max_error = trigger_xs.iter()
+            .map(|x| {
+                let expected = self.time_at(*x) - self.time_at(first);
+                let actual = from_start.time_at(*x) - from_start.time_at(first);
+                (actual - expected).abs()
+            })
+            .fold(0.0, f32::max);
+        if max_error > DESYNC_TOLERANCE {
+            StartPosCheck::Desync(max_error)
+        }
+        else {
+            StartPosCheck::Ok
+        }
     }
 }
+/// A start position object (id 31), which lets the player start partway through the level
+#[derive(Clone, Debug, PartialEq)]
+pub struct StartPos {
+    /// The index of the object in the level
+    pub index: usize,
+    pub x: f32,
+    /// The speed setting of the start position (`kA4`)
+    pub speed: Speed
+}
+impl StartPos {
+    /// Finds the start positions in a level, sorted by x
+    pub fn find(objects: &[Obj]) -> Vec<StartPos> {
+        let mut positions: Vec<StartPos> = objects.iter().enumerate()
+            .filter(|(_, o)| matches!(o.get(ObjID), Some(Int(START_POS_ID))))
+            .map(|(index, o)| StartPos {
+                index,
+                x: o.get_pos().0,
+                speed: o.get_raw("kA4")
+                    .and_then(|v| v.parse().ok())
+                    .and_then(Speed::from_header)
+                    .unwrap_or(Normal)
+            })
+            .collect();
+        positions.sort_by(|a, b| a.x.total_cmp(&b.x));
+        positions
+    }
+}
+/// Whether generated motion plays back correctly from a start position
 #[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StartPosCheck {
+    Ok,
+    /// The start position is past the first trigger at this x, so the motion never starts
+    AfterMotion(f32),
+    /// The triggers fire up to this many seconds away from when they do in a full playthrough
+    Desync(f32)
+}
+impl std::fmt::Display for StartPosCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartPosCheck::Ok => write!(f, "plays correctly"),
+            StartPosCheck::AfterMotion(x) => write!(f, "starts after the motion begins at x = {x}"),
+            StartPosCheck::Desync(error) => write!(f, "triggers fire up to {error:.3}s off")
+        }
+    }
+}
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Speed {
     Half, Normal, Double, Triple, Quad
 }
@@ -45,6 +199,10 @@ impl Speed {
             Speed::Quad => 19.201,
         }
     }
+    /// The speed in editor units (a block is 30 units) per second
+    pub fn units_per_second(&self) -> f32 {
+        self.bps() * 30.0
+    }
     pub fn from(id: i32) -> Option<Speed> {
         match id {
             200 => Some(Half),
@@ -55,6 +213,26 @@ impl Speed {
             _ => None
         }
     }
+    /// Converts the speed setting of a level header or start position (`kA4`)
+    pub fn from_header(id: i32) -> Option<Speed> {
+        match id {
+            0 => Some(Normal),
+            1 => Some(Half),
+            2 => Some(Double),
+            3 => Some(Triple),
+            4 => Some(Quad),
+            _ => None
+        }
+    }
+    pub fn header_id(&self) -> i32 {
+        match self {
+            Normal => 0,
+            Half => 1,
+            Double => 2,
+            Triple => 3,
+            Quad => 4
+        }
+    }
     pub fn width(&self) -> f32 {
         match self {
             Half => 34.0,
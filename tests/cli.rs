@@ -0,0 +1,106 @@
+use gdphysics::{Config, LayerConfig, ObjectConfig, cli::{self, Args, Command}, config::{CONFIG_PARAMS, LAYER_PARAMS, OBJECT_PARAMS, Param}};
+
+fn parse(args: &str) -> Result<Args, String> {
+    Args::parse(args.split_whitespace().map(String::from))
+}
+
+fn test_config() -> Config {
+    serde_json::from_str(r#"{
+        "level_position": 2,
+        "path": "save.dat",
+        "backup_path": "backup.dat",
+        "simulations": [{ "layer": 1 }, { "layer": 2, "sim_time": 3.0 }]
+    }"#).unwrap()
+}
+
+#[test]
+fn parses_subcommands() {
+    for (name, command) in [("simulate", Command::Simulate), ("restore", Command::Restore), ("list", Command::List),
+        ("inspect", Command::Inspect), ("clean", Command::Clean), ("export", Command::Export)] {
+        let args = parse(&format!("{name} config.json")).unwrap();
+        assert_eq!(args.command, command);
+        assert_eq!(args.config_path.as_deref(), Some("config.json"));
+    }
+    let args = parse("export config.json out.gmd").unwrap();
+    assert_eq!(args.export_path.as_deref(), Some("out.gmd"));
+}
+
+#[test]
+fn parses_old_style() {
+    assert_eq!(parse("config.json").unwrap().command, Command::Simulate);
+    assert_eq!(parse("config.json restore").unwrap().command, Command::Restore);
+    assert_eq!(parse("config.json list").unwrap().command, Command::List);
+    assert!(parse("config.json export").is_err());
+}
+
+#[test]
+fn parses_flags() {
+    let args = parse("simulate config.json --level Test --layer 2 --layer 3 --sim-time 1.5 --save other.dat --dry-run").unwrap();
+    assert_eq!(args.level.as_deref(), Some("Test"));
+    assert_eq!(args.layers, vec![2, 3]);
+    assert_eq!(args.sim_time, Some(1.5));
+    assert_eq!(args.save.as_deref(), Some("other.dat"));
+    assert!(args.dry_run);
+    // Flags can come first
+    assert_eq!(parse("--dry-run config.json").unwrap().config_path.as_deref(), Some("config.json"));
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    assert!(parse("simulate").is_err());
+    assert!(parse("simulate config.json --layer").is_err());
+    assert!(parse("simulate config.json --layer two").is_err());
+    assert!(parse("simulate config.json --sim-time -1").is_err());
+    assert!(parse("simulate config.json --sim-time NaN").is_err());
+    assert!(parse("simulate config.json --sim-time inf").is_err());
+    assert!(parse("simulate config.json --verbose").is_err());
+    assert!(parse("simulate config.json extra").is_err());
+    assert!(parse("config.json frobnicate").is_err());
+}
+
+#[test]
+fn help() {
+    assert_eq!(parse("").unwrap().command, Command::Help);
+    assert_eq!(parse("simulate config.json --help").unwrap().command, Command::Help);
+    assert_eq!(parse("help").unwrap().command, Command::Help);
+    let help = cli::help();
+    for param in CONFIG_PARAMS.iter().chain(LAYER_PARAMS).chain(OBJECT_PARAMS) {
+        assert!(help.contains(param.name), "{} is missing from the help", param.name);
+    }
+}
+
+#[test]
+fn applies_overrides() {
+    let mut config = test_config();
+    config.level_index = Some(1);
+    config.level_revision = Some(3);
+    parse("simulate config.json --level Test --layer 2 --sim-time 0.5 --save other.dat").unwrap()
+        .apply(&mut config).unwrap();
+    assert_eq!(config.level_name.as_deref(), Some("Test"));
+    assert_eq!((config.level_index, config.level_revision, config.level_position), (None, None, None));
+    assert_eq!(config.path, "other.dat");
+    assert_eq!(config.simulations.len(), 1);
+    assert_eq!(config.simulations[0].layer, 2);
+    assert_eq!(config.simulations[0].sim_time, 0.5);
+
+    let mut config = test_config();
+    assert!(parse("simulate config.json --layer 7").unwrap().apply(&mut config).is_err());
+}
+
+fn assert_documented(value: serde_json::Value, params: &[Param]) {
+    let fields = value.as_object().unwrap();
+    for field in fields.keys() {
+        assert!(params.iter().any(|p| p.name == field), "{field} isn't documented");
+    }
+    for param in params {
+        assert!(fields.contains_key(param.name), "{} isn't a config field", param.name);
+    }
+}
+
+#[test]
+fn params_match_config() {
+    assert_documented(serde_json::to_value(test_config()).unwrap(), CONFIG_PARAMS);
+    let layer: LayerConfig = serde_json::from_str(r#"{ "layer": 1 }"#).unwrap();
+    assert_documented(serde_json::to_value(layer).unwrap(), LAYER_PARAMS);
+    assert_documented(serde_json::to_value(ObjectConfig::new(1)).unwrap(), OBJECT_PARAMS);
+}
@@ -0,0 +1,271 @@
+use gdphysics::{Config, LayerConfig, ObjectConfig, config::{validate, ConfigProblem}};
+use serde_json::json;
+
+fn problems(value: serde_json::Value) -> Vec<String> {
+    validate(&value).iter().map(ConfigProblem::to_string).collect()
+}
+
+fn base() -> serde_json::Value {
+    json!({
+        "level_name": "Example",
+        "path": "save.dat",
+        "backup_path": "backup.dat",
+        "simulations": [{ "layer": 1, "objects": [{ "group": 5 }] }]
+    })
+}
+
+#[test]
+fn valid_config_has_no_problems() {
+    assert!(problems(base()).is_empty());
+    let example = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/example_config.json")).unwrap();
+    assert_eq!(problems(serde_json::from_str(&example).unwrap()), Vec::<String>::new());
+}
+
+#[test]
+fn suggests_known_fields() {
+    let mut config = base();
+    config["simulations"][0]["objects"][0]["initial_velocity"] = json!([200.0, 300.0]);
+    config["simulations"][0]["objects"][0]["positon_fixed"] = json!(true);
+    config["simulations"][0]["gravty"] = json!([0, -10]);
+    config["levle_name"] = json!("Typo");
+    config["colour"] = json!(1);
+    assert_eq!(problems(config), vec![
+        "colour: unknown field \"colour\"",
+        "levle_name: unknown field \"levle_name\", did you mean \"level_name\"?",
+        "simulations[0].gravty: unknown field \"gravty\", did you mean \"gravity\"?",
+        "simulations[0].objects[0].initial_velocity: unknown field \"initial_velocity\", did you mean \"velocity\"?",
+        "simulations[0].objects[0].positon_fixed: unknown field \"positon_fixed\", did you mean \"position_fixed\"?",
+    ]);
+}
+
+#[test]
+fn checks_ranges_and_types() {
+    let mut config = base();
+    config["simulations"][0]["fps"] = json!(0);
+    config["simulations"][0]["keyframe_interval"] = json!(0);
+    config["simulations"][0]["sim_time"] = json!(-1.0);
+    config["simulations"][0]["objects"][0]["density"] = json!(0.0);
+    config["simulations"][0]["ground"] = json!("yes");
+    config["simulations"][0]["gravity"] = json!([0.0]);
+    config["level_name"] = json!(null);
+    assert_eq!(problems(config), vec![
+        "simulations[0].fps: must be greater than 0",
+        "simulations[0].gravity: expected [float, float], found [0.0]",
+        "simulations[0].ground: expected bool, found \"yes\"",
+        "simulations[0].keyframe_interval: must be at least 1",
+        "simulations[0].sim_time: must be greater than 0",
+        "simulations[0].objects[0].density: must be greater than 0",
+    ]);
+}
+
+#[test]
+fn reports_missing_fields() {
+    let config = json!({ "path": "save.dat", "simulations": [{ "objects": [{}] }, 3] });
+    assert_eq!(problems(config), vec![
+        "backup_path: missing required field",
+        "simulations[0].layer: missing required field",
+        "simulations[0].objects[0].group: missing required field",
+        "simulations[1]: must be an object",
+    ]);
+}
+
+#[test]
+fn load_reports_every_problem() {
+    let mut config = base();
+    config["simulations"][0]["fps"] = json!(0);
+    config["simulations"][0]["objects"][0]["initial_velocity"] = json!([1, 2]);
+    let path = std::env::temp_dir().join(format!("gdphysics-config-{}.json", std::process::id()));
+    std::fs::write(&path, config.to_string()).unwrap();
+    let error = Config::load(path.to_string_lossy().to_string()).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    let message = error.to_string();
+    assert!(message.contains("simulations[0].fps: must be greater than 0"), "{message}");
+    assert!(message.contains("did you mean \"velocity\""), "{message}");
+}
+
+/// A fresh directory for config files
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("gdphysics-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("presets")).unwrap();
+    dir
+}
+
+fn write(dir: &std::path::Path, name: &str, value: serde_json::Value) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, value.to_string()).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn extends_base_files() {
+    let dir = temp_dir("extends");
+    write(&dir, "presets/team.json", json!({
+        "backup_path": "team-backup.dat",
+        "defaults": { "friction": 0.3, "linear_damping": 0.5, "gravity": [0, -100] }
+    }));
+    write(&dir, "presets/bouncy.json", json!({
+        "defaults": { "restitution": 0.8, "friction": 0.2 }
+    }));
+    let path = write(&dir, "level.json", json!({
+        "extends": ["presets/team.json", "presets/bouncy.json"],
+        "level_name": "Example",
+        "path": "save.dat",
+        "defaults": { "fps": 120 },
+        "simulations": [
+            { "layer": 1, "objects": [{ "group": 5 }, { "group": 6, "friction": 0.9 }] },
+            { "layer": 2, "gravity": [0, 0], "object_defaults": { "density": 2.0, "friction": 0.1 } }
+        ]
+    }));
+    let config = Config::load(path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(config.backup_path, "team-backup.dat");
+    assert_eq!(config.extends, vec!["presets/team.json", "presets/bouncy.json"]);
+    let (first, second) = (&config.simulations[0], &config.simulations[1]);
+    assert_eq!(first.fps, 120.0);
+    assert_eq!(first.gravity, [0.0, -100.0]);
+    assert_eq!(second.gravity, [0.0, 0.0]);
+    // Later base files override earlier ones
+    assert_eq!(first.objects[0].friction, 0.2);
+    assert_eq!(first.objects[0].restitution, 0.8);
+    assert_eq!(first.objects[0].linear_damping, 0.5);
+    assert_eq!(first.objects[1].friction, 0.9);
+    // Bodies without an entry use the layer's defaults
+    let unlisted = ObjectConfig::with_defaults(7, &second.object_defaults);
+    assert_eq!((unlisted.group, unlisted.friction, unlisted.density, unlisted.restitution), (7, 0.1, 2.0, 0.8));
+}
+
+#[test]
+fn extends_loop_is_an_error() {
+    let dir = temp_dir("loop");
+    write(&dir, "a.json", json!({ "extends": "b.json" }));
+    write(&dir, "b.json", json!({ "extends": "a.json" }));
+    let error = Config::load(dir.join("a.json").to_string_lossy().to_string()).unwrap_err();
+    let missing = Config::load(write(&dir, "c.json", json!({ "extends": "missing.json" }))).unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(error.to_string().contains("loop"), "{error}");
+    assert!(missing.to_string().contains("missing.json"), "{missing}");
+}
+
+#[test]
+fn validates_defaults() {
+    let mut config = base();
+    config["defaults"] = json!({ "fricton": 0.5, "density": 0, "layer": 3 });
+    config["simulations"][0]["object_defaults"] = json!({ "group": 1, "velocty": [1, 1] });
+    assert_eq!(problems(config), vec![
+        "defaults.density: must be greater than 0",
+        "defaults.fricton: unknown field \"fricton\", did you mean \"friction\"?",
+        "defaults.layer: unknown field \"layer\"",
+        "simulations[0].object_defaults.group: unknown field \"group\"",
+        "simulations[0].object_defaults.velocty: unknown field \"velocty\", did you mean \"velocity\"?",
+    ]);
+}
+
+fn load(name: &str, value: serde_json::Value) -> Result<Config, std::io::Error> {
+    let dir = temp_dir(name);
+    let result = Config::load(write(&dir, "config.json", value));
+    std::fs::remove_dir_all(&dir).unwrap();
+    result
+}
+
+#[test]
+fn objects_use_materials() {
+    let mut config = base();
+    config["materials"] = json!({
+        "slime": { "restitution": 0.1, "friction": 2.0, "linear_damping": 0.9 },
+        "ice": { "friction": 0.0 }
+    });
+    config["defaults"] = json!({ "density": 3.0, "material": "heavy_crate" });
+    config["simulations"][0]["objects"] = json!([
+        { "group": 5, "material": "slime" },
+        { "group": 6, "material": "slime", "friction": 1.5 },
+        { "group": 7, "material": "bouncy_ball" },
+        { "group": 8, "material": "ice" }
+    ]);
+    config["simulations"].as_array_mut().unwrap().push(json!({ "layer": 2, "object_defaults": { "material": "wood" }, "objects": [{ "group": 5 }] }));
+    let config = load("materials", config).unwrap();
+    let layer = &config.simulations[0];
+    let objects = &layer.objects;
+    assert_eq!((objects[0].restitution, objects[0].friction, objects[0].linear_damping), (0.1, 2.0, 0.9));
+    // The body's own values override its material
+    assert_eq!(objects[1].friction, 1.5);
+    // And the material overrides the layer's defaults
+    assert_eq!((objects[2].restitution, objects[2].density), (0.9, 0.5));
+    // Only the parameters the material sets are taken from it, and explicit defaults
+    // override the defaults' own material
+    assert_eq!((objects[0].density, objects[0].angular_damping), (3.0, 0.3));
+    // Custom materials override built-in ones field by field
+    assert_eq!((objects[3].friction, objects[3].restitution), (0.0, 0.05));
+    assert_eq!(objects[3].material.as_deref(), Some("ice"));
+    let unlisted = ObjectConfig::with_defaults(9, &layer.object_defaults);
+    assert_eq!((unlisted.density, unlisted.friction), (3.0, 0.8));
+    // A layer's material overrides the shared defaults
+    // Text objects in the level can name the same materials
+    let library = config.material_library();
+    assert_eq!((library["slime"]["friction"].as_f64(), library["ice"]["restitution"].as_f64()), (Some(2.0), Some(0.05)));
+    let wood = &config.simulations[1].objects[0];
+    assert_eq!((wood.density, wood.friction, wood.angular_damping), (0.7, 0.6, 0.3));
+}
+
+#[test]
+fn validates_materials() {
+    let mut config = base();
+    config["materials"] = json!({ "slime": { "frictin": 2.0, "group": 3 } });
+    config["defaults"] = json!({ "material": "rubbr" });
+    config["simulations"][0]["objects"][0]["material"] = json!("bouncy");
+    config["simulations"][0]["object_defaults"] = json!({ "material": "slime" });
+    config["simulations"][0]["objects"] = json!([{ "group": 5, "material": "bouncy" }, { "group": 6, "material": "xyz" }]);
+    let problems = problems(config);
+    assert_eq!(problems[..4], [
+        "materials.slime.frictin: unknown field \"frictin\", did you mean \"friction\"?",
+        "materials.slime.group: unknown field \"group\"",
+        "defaults.material: unknown material \"rubbr\", did you mean \"rubber\"?",
+        "simulations[0].objects[0].material: unknown material \"bouncy\", did you mean \"bouncy_ball\"?",
+    ]);
+    assert!(problems[4].starts_with("simulations[0].objects[1].material: unknown material \"xyz\", the materials are "), "{}", problems[4]);
+    assert_eq!(problems.len(), 5);
+}
+
+#[test]
+fn selects_groups() {
+    let layer = |json: &str| -> LayerConfig { serde_json::from_str(json).unwrap() };
+    let all = layer(r#"{ "layer": 1 }"#);
+    assert!(all.selects(5) && all.selects(900));
+    assert_eq!(all.trigger_layer(), 1);
+    let some = layer(r#"{ "layer": 1, "groups": [3, 5], "group_range": [10, 12], "trigger_layer": 2 }"#);
+    assert_eq!([3, 4, 5, 9, 10, 12, 13].map(|g| some.selects(g)), [true, false, true, false, true, true, false]);
+    assert_eq!(some.trigger_layer(), 2);
+}
+
+#[test]
+fn trigger_layers_are_not_shared() {
+    let mut config = base();
+    config["simulations"] = json!([
+        { "layer": 1, "groups": [5] },
+        { "layer": 1, "groups": [6], "trigger_layer": 2 },
+        { "layer": 3, "trigger_layer": 1, "group_range": [9, 4] }
+    ]);
+    assert_eq!(problems(config), [
+        "simulations[2].trigger_layer: simulations[0] already places its triggers on layer 1, set a different trigger_layer",
+        "simulations[2].group_range: the first group must not be above the last",
+    ]);
+}
+
+#[test]
+fn bodies_are_not_simulated_twice() {
+    let mut config = base();
+    config["simulations"] = json!([
+        { "layer": 1 },
+        { "layer": 1, "groups": [7], "trigger_layer": 3 },
+        { "layer": 2, "groups": [3, 9] },
+        { "layer": 2, "group_range": [4, 8], "trigger_layer": 4 },
+        { "layer": 2, "group_range": [8, 12], "trigger_layer": 5 },
+        { "layer": 2, "trigger_layer": 6 }
+    ]);
+    assert_eq!(problems(config), [
+        "simulations[1].groups: simulations[0] already simulates group 7 on layer 1, select different groups",
+        "simulations[4].groups: simulations[2] already simulates group 9 on layer 2, select different groups",
+        "simulations[5].groups: simulations[2] already simulates group 3 on layer 2, select different groups",
+    ]);
+}
@@ -0,0 +1,108 @@
+use gdphysics::{LayerConfig, config::builtin_materials, Level, Obj, generated, object::{AttribKey::*, AttribValue::*}, physics, speed::SpeedTracker};
+
+/// The bodies from `tests/data/bodies.txt` and a hand made move trigger on the simulated layer
+fn level() -> Level {
+    let mut level = Level::try_from(include_str!("data/bodies.txt").to_string()).unwrap();
+    level.objects.push(Obj::new(901, 600.0, 90.0).with(EditorLayer1, Int(1)).with(MoveOffsetX, Int(30)).with(TargetGroupID, Int(3)));
+    level
+}
+
+fn simulate(level: &mut Level, layer: u16) {
+    let config: LayerConfig = serde_json::from_str(&format!(
+        r#"{{ "layer": {layer}, "sim_time": 1.0, "objects": [{{ "group": 5, "velocity": [100, 100] }}] }}"#
+    )).unwrap();
+    let tracker = SpeedTracker::new(&level.objects, level.header.speed());
+    physics::simulate(&mut level.objects, config, &tracker, &builtin_materials());
+}
+
+fn count(level: &Level, id: i32) -> usize {
+    level.objects.iter().filter(|o| matches!(o.get(ObjID), Some(Int(i)) if *i == id)).count()
+}
+
+fn hand_made(level: &Level) -> usize {
+    level.objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(901))) && o.get_pos() == (600.0, 90.0))
+        .count()
+}
+
+#[test]
+fn text_round_trip() {
+    let mut obj = Obj::new(914, 0.0, 0.0);
+    obj.set_text("hello world?");
+    assert_eq!(obj.text().as_deref(), Some("hello world?"));
+    assert_eq!(Obj::try_from(obj.as_str()).unwrap().text().as_deref(), Some("hello world?"));
+}
+
+#[test]
+fn rerun_keeps_hand_made_triggers() {
+    let mut level = level();
+    simulate(&mut level, 1);
+    let generated_count = level.objects.len();
+    let group = generated::generated_group(&level.objects).unwrap();
+    // The bodies and the hand made trigger use groups up to 8
+    assert_eq!(group, 9);
+    assert!(count(&level, 901) > 2);
+
+    simulate(&mut level, 1);
+    assert_eq!(level.objects.len(), generated_count);
+    assert_eq!(hand_made(&level), 1);
+    assert_eq!(count(&level, 914), 1);
+    let marker = level.objects.iter().find(|o| matches!(o.get(ObjID), Some(Int(914)))).unwrap();
+    // The marker sits below the ground where players can't see it
+    assert!(marker.get_pos().1 < 0.0);
+    assert_eq!(generated::generated_group(&level.objects), Some(group));
+}
+
+#[test]
+fn survives_serialization() {
+    let mut level = level();
+    simulate(&mut level, 1);
+    let mut level = Level::try_from(level.as_str()).unwrap();
+    let len = level.objects.len();
+    simulate(&mut level, 1);
+    assert_eq!(level.objects.len(), len);
+}
+
+#[test]
+fn clean_removes_generated_objects() {
+    let mut level = level();
+    let original = level.objects.len();
+    simulate(&mut level, 1);
+    // Another layer with a copy of the body
+    let copies: Vec<Obj> = level.objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(1764))))
+        .map(|o| Obj::try_from(o.as_str()).unwrap().with(EditorLayer1, Int(2)))
+        .collect();
+    let copied = copies.len();
+    level.objects.extend(copies);
+    simulate(&mut level, 2);
+    let with_both = level.objects.len();
+
+    let removed = generated::clean(&mut level.objects, Some(2));
+    assert!(removed > 0);
+    assert_eq!(level.objects.len(), with_both - removed);
+    assert!(count(&level, 901) > 2);
+
+    generated::clean(&mut level.objects, None);
+    assert_eq!(level.objects.len(), original + copied);
+    assert_eq!(hand_made(&level), 1);
+    assert_eq!(count(&level, 914), 0);
+    assert_eq!(generated::generated_group(&level.objects), None);
+}
+
+#[test]
+fn first_run_removes_untagged_triggers_from_older_versions() {
+    let legacy = "1,901,2,330,3,2100,20,1,28,3,29,3,51,5,10,0.1,108,1;1,1346,2,330,3,2130,20,1,68,10,51,5,71,6,10,0.09,108,1;1,901,2,330,3,2100,20,2,51,8,108,1;";
+    let mut level = level();
+    level.objects.extend(legacy.split(';').filter(|o| !o.is_empty()).map(|o| Obj::try_from(o.to_string()).unwrap()));
+    assert_eq!(generated::clean(&mut level.objects, None), 0);
+    assert_eq!(generated::clean(&mut level.objects, Some(1)), 2);
+    assert_eq!(hand_made(&level), 1);
+    // The trigger on layer 2 isn't on the cleaned layer
+    assert_eq!(count(&level, 901), 2);
+
+    simulate(&mut level, 1);
+    // Once the level has a marker, only tagged objects are removed
+    assert_eq!(generated::clean(&mut level.objects, Some(2)), 0);
+    assert_eq!(hand_made(&level), 1);
+}
@@ -0,0 +1,51 @@
+use gdphysics::{Level, LevelHeader, header::{ColorChannel, GameMode}, speed::Speed};
+
+const HEADER: &str = "kS38,1_40_2_125_3_255_11_255_12_255_13_255_4_-1_6_1000_7_1_15_1_18_0_8_1|1_0_2_102_3_255_11_255_12_255_13_255_4_-1_6_1001_7_1_15_1_18_0_8_1|,kA13,1.5,kA15,0,kA16,0,kA14,10~0.9~20~0.8,kA6,0,kA7,0,kA17,0,kA18,0,kS39,0,kA2,2,kA3,1,kA8,0,kA4,3,kA9,0,kA10,1,kA11,0";
+
+#[test]
+fn parse_header() {
+    let header = LevelHeader::from(HEADER);
+    assert_eq!(header.speed(), Speed::Triple);
+    assert_eq!(header.game_mode(), GameMode::Ball);
+    assert_eq!(header.song_offset(), 1.5);
+    assert!(header.mini());
+    assert!(!header.dual());
+    assert!(header.two_player());
+    assert!(!header.platformer());
+    assert!(LevelHeader::from("kA2,0,kA22,1").platformer());
+    assert_eq!(header.guidelines(), "10~0.9~20~0.8");
+    let colors = header.colors();
+    assert_eq!(colors.len(), 2);
+    assert_eq!(colors[0].id(), Some(1000));
+    assert_eq!(colors[0].rgb(), Some((40, 125, 255)));
+    assert_eq!(header.color(1001).unwrap().rgb(), Some((0, 102, 255)));
+    assert_eq!(header.as_str(), HEADER);
+}
+
+#[test]
+fn edit_header() {
+    let mut header = LevelHeader::from(HEADER);
+    header.set_speed(Speed::Half);
+    header.set_game_mode(GameMode::Wave);
+    header.set_dual(true);
+    let mut bg = header.color(1000).unwrap();
+    bg.set_rgb(10, 20, 30);
+    header.set_color(bg);
+    header.set_color(ColorChannel::new(1));
+
+    let header = LevelHeader::from(header.as_str().as_str());
+    assert_eq!(header.speed(), Speed::Half);
+    assert_eq!(header.game_mode(), GameMode::Wave);
+    assert!(header.dual());
+    assert_eq!(header.color(1000).unwrap().rgb(), Some((10, 20, 30)));
+    assert_eq!(header.color(1).unwrap().rgb(), Some((255, 255, 255)));
+    assert_eq!(header.colors().len(), 3);
+}
+
+#[test]
+fn level_header_round_trip() {
+    let ls = format!("{HEADER};1,1,2,15,3,15;");
+    let level = Level::try_from(ls.clone()).unwrap();
+    assert_eq!(level.header.speed(), Speed::Triple);
+    assert_eq!(level.as_str(), ls);
+}
@@ -0,0 +1,191 @@
+use gdphysics::{GdError, levelstring::*};
+
+const LEVEL: &str = "kA13,0,kA15,0,kA16,0,kA14,,kA6,0,kA7,0,kA4,0;1,1,2,45,3,15;";
+
+fn save_xml(levels: &[(&str, Option<&str>)]) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0"?><plist version="1.0" gjver="2.0"><dict><k>LLM_01</k><d><k>_isArr</k><t /><k>kCEK</k><i>4</i>"#);
+    for (i, (name, ls)) in levels.iter().enumerate() {
+        xml += &format!("<k>k_{i}</k><d><k>kCEK</k><i>4</i><k>k2</k><s>{name}</s>");
+        if let Some(ls) = ls {
+            xml += &format!("<k>k4</k><s>{}</s>", encode_level_string(ls).unwrap());
+        }
+        xml += "<k>k46</k><i>3</i></d>";
+    }
+    xml + "</d><k>LLM_02</k><i>35</i></dict></plist>"
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("gdphysics-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn savefile_round_trip() {
+    let xml = save_xml(&[("Example", Some(LEVEL))]);
+    for format in [SaveFormat::Windows, SaveFormat::Mac] {
+        let encrypted = encrypt_savefile(xml.as_bytes(), format).unwrap();
+        assert_eq!(decrypt_savefile(encrypted, format).unwrap(), xml.as_bytes());
+    }
+}
+
+#[test]
+fn mac_padding_fills_whole_block() {
+    let data = [b'a'; 32];
+    let encrypted = encrypt_savefile(&data, SaveFormat::Mac).unwrap();
+    assert_eq!(encrypted.len(), 48);
+    assert_eq!(decrypt_savefile(encrypted, SaveFormat::Mac).unwrap(), data);
+}
+
+#[test]
+fn mac_rejects_wrong_format() {
+    let xml = save_xml(&[("Example", Some(LEVEL))]);
+    let encrypted = encrypt_savefile(xml.as_bytes(), SaveFormat::Windows).unwrap();
+    assert!(decrypt_savefile(encrypted, SaveFormat::Mac).is_err());
+}
+
+#[test]
+fn level_string_round_trip() {
+    let encoded = encode_level_string(LEVEL).unwrap();
+    assert!(encoded.starts_with("H4sIAAAAAAAAC"));
+    assert_eq!(decode_level_string(&encoded).unwrap(), LEVEL);
+}
+
+#[test]
+fn write_level_to_mac_save() {
+    let xml = save_xml(&[("Other", Some(LEVEL)), ("Example", Some(LEVEL))]);
+    let path = temp_path("mac.dat");
+    std::fs::write(&path, encrypt_savefile(xml.as_bytes(), SaveFormat::Mac).unwrap()).unwrap();
+
+    let selector = LevelSelector::Name("Example".to_string());
+    let data = std::fs::read(&path).unwrap();
+    assert_eq!(get_level_string(data, &selector, SaveFormat::Mac).unwrap(), LEVEL);
+
+    let new_ls = "kA13,0;1,1,2,75,3,15;".to_string();
+    encrypt_level_string(new_ls.clone(), String::new(), path.clone(), &selector, SaveFormat::Mac).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    assert_eq!(get_level_string(data.clone(), &selector, SaveFormat::Mac).unwrap(), new_ls);
+    let other = LevelSelector::Name("Other".to_string());
+    assert_eq!(get_level_string(data, &other, SaveFormat::Mac).unwrap(), LEVEL);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn detect_save_format() {
+    let xml = save_xml(&[("Example", Some(LEVEL))]);
+    for format in [SaveFormat::Windows, SaveFormat::Mac, SaveFormat::Plain] {
+        let encrypted = encrypt_savefile(xml.as_bytes(), format).unwrap();
+        assert_eq!(SaveFormat::detect(&encrypted), Some(format));
+    }
+    assert_eq!(SaveFormat::detect(b"not a save file"), None);
+}
+
+#[test]
+fn list_levels_in_save() {
+    let xml = save_xml(&[("Example", Some(LEVEL)), ("Empty", None), ("Example", Some("kA4,0;"))]);
+    let levels = list_levels(xml.into_bytes(), SaveFormat::Plain).unwrap();
+    assert_eq!(levels.len(), 3);
+    assert_eq!(levels[0].name, "Example");
+    assert_eq!(levels[0].revision, Some(3));
+    assert_eq!(levels[0].object_count, Some(1));
+    assert!(levels[0].initialized);
+    assert_eq!(levels[1].name, "Empty");
+    assert!(!levels[1].initialized);
+    assert_eq!(levels[2].index, 2);
+    assert_eq!(levels[2].object_count, Some(0));
+    assert!(levels.iter().all(|l| l.unreadable.is_none()));
+}
+
+#[test]
+fn list_unreadable_levels() {
+    let xml = save_xml(&[("Example", Some(LEVEL)), ("Broken", Some(LEVEL)), ("Other", Some("kA4,0;"))]);
+    let encoded = encode_level_string(LEVEL).unwrap();
+    let xml = xml.replacen(&format!("Broken</s><k>k4</k><s>{encoded}"), "Broken</s><k>k4</k><s>not a level", 1);
+    let levels = list_levels(xml.into_bytes(), SaveFormat::Plain).unwrap();
+    assert_eq!(levels.len(), 3);
+    assert_eq!((levels[1].name.as_str(), levels[1].object_count, levels[1].initialized), ("Broken", None, true));
+    assert!(levels[1].unreadable.is_some());
+    assert_eq!((levels[0].object_count, levels[2].object_count), (Some(1), Some(0)));
+}
+
+#[test]
+fn select_levels_with_duplicate_names() {
+    let xml = save_xml(&[("Example", Some(LEVEL)), ("Other", None), ("Example", Some("kA4,1;"))])
+        .replacen("<k>k46</k><i>3</i>", "<k>k46</k><i>7</i>", 1);
+    let data = xml.into_bytes();
+    let get = |selector| get_level_string(data.clone(), &selector, SaveFormat::Plain);
+
+    match get(LevelSelector::Name("Example".to_string())) {
+        Err(GdError::AmbiguousLevel { candidates, .. }) => assert_eq!(candidates, vec![(0, Some(7)), (2, Some(3))]),
+        _ => panic!("expected an ambiguous level error")
+    }
+    assert_eq!(get(LevelSelector::NameIndex("Example".to_string(), 1)).unwrap(), "kA4,1;");
+    assert_eq!(get(LevelSelector::NameRevision("Example".to_string(), 7)).unwrap(), LEVEL);
+    assert_eq!(get(LevelSelector::Position(2)).unwrap(), "kA4,1;");
+    assert!(matches!(get(LevelSelector::Position(1)), Err(GdError::LevelUninitialized(_))));
+    assert!(matches!(get(LevelSelector::Position(3)), Err(GdError::LevelNotFound(_))));
+    assert!(matches!(get(LevelSelector::NameIndex("Example".to_string(), 2)), Err(GdError::LevelNotFound(_))));
+}
+
+#[test]
+fn writing_only_changes_the_selected_level() {
+    let xml = save_xml(&[("Example", Some(LEVEL)), ("Example", Some(LEVEL))]);
+    let path = temp_path("plain.xml");
+    std::fs::write(&path, &xml).unwrap();
+
+    let selector = LevelSelector::NameIndex("Example".to_string(), 1);
+    encrypt_level_string("kA4,2;".to_string(), String::new(), path.clone(), &selector, SaveFormat::Plain).unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    let encoded = encode_level_string(LEVEL).unwrap();
+    let second = xml.rfind(&encoded).unwrap();
+    assert_eq!(written[..second], xml[..second]);
+    assert_eq!(written[second + written.len() - xml.len() + encoded.len()..], xml[second + encoded.len()..]);
+
+    let data = written.into_bytes();
+    assert_eq!(get_level_string(data.clone(), &selector, SaveFormat::Plain).unwrap(), "kA4,2;");
+    assert_eq!(get_level_string(data, &LevelSelector::Position(0), SaveFormat::Plain).unwrap(), LEVEL);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn export_and_import_gmd() {
+    let xml = save_xml(&[("Other", None), ("Example", Some(LEVEL))]);
+    let save = encrypt_savefile(xml.as_bytes(), SaveFormat::Windows).unwrap();
+    let selector = LevelSelector::Name("Example".to_string());
+    let gmd = export_gmd(save.clone(), &selector, SaveFormat::Windows, "kA4,1;").unwrap();
+    assert!(gmd.starts_with(r#"<?xml version="1.0"?><plist version="1.0" gjver="2.0"><dict><k>kCEK</k><i>4</i><k>k2</k><s>Example</s>"#));
+    assert!(gmd.ends_with("<k>k46</k><i>3</i></dict></plist>"));
+    assert_eq!(read_gmd(gmd.clone().into_bytes()).unwrap(), "kA4,1;");
+    assert_eq!(SaveFormat::detect(gmd.as_bytes()), Some(SaveFormat::Plain));
+
+    let empty = export_gmd(save, &LevelSelector::Name("Other".to_string()), SaveFormat::Windows, LEVEL).unwrap();
+    assert_eq!(read_gmd(empty.into_bytes()).unwrap(), LEVEL);
+
+    let path = temp_path("level.gmd");
+    std::fs::write(&path, &gmd).unwrap();
+    encrypt_level_string(LEVEL.to_string(), String::new(), path.clone(), &LevelSelector::Position(0), SaveFormat::Plain).unwrap();
+    assert_eq!(read_gmd(std::fs::read(&path).unwrap()).unwrap(), LEVEL);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn export_gmd_keeps_full_tags() {
+    let xml = r#"<?xml version="1.0"?><plist version="1.0" gjver="2.0"><dict><key>LLM_01</key><dict><key>k_0</key><dict><key>k2</key><string>Full</string><key>k46</key><integer>3</integer></dict></dict></dict></plist>"#;
+    let gmd = export_gmd(xml.as_bytes().to_vec(), &LevelSelector::Position(0), SaveFormat::Plain, LEVEL).unwrap();
+    assert!(!gmd.contains("<k>") && !gmd.contains("<s>"), "{gmd}");
+    assert!(gmd.contains("<key>k4</key><string>"));
+    assert_eq!(read_gmd(gmd.into_bytes()).unwrap(), LEVEL);
+}
+
+#[test]
+fn plain_level_strings_are_not_saves() {
+    assert_eq!(SaveFormat::detect(LEVEL.as_bytes()), None);
+    assert!(is_plain_level_string(LEVEL.as_bytes()));
+    assert!(is_plain_level_string(b"\nkA4,3;"));
+    assert!(!is_plain_level_string(b"<?xml version=\"1.0\"?>"));
+}
+
+#[test]
+fn gmd_from_plain_level_string() {
+    let gmd = gmd_from_level_string("Tom & Jerry", LEVEL).unwrap();
+    assert!(gmd.contains("<k>k2</k><s>Tom &amp; Jerry</s>"), "{gmd}");
+    assert_eq!(read_gmd(gmd.into_bytes()).unwrap(), LEVEL);
+}
@@ -0,0 +1,68 @@
+use gdphysics::{GdError, Level, Obj, object::{AttribKey::*, AttribValue::*}};
+
+// A start position, a block with GD 2.2 scale/layer keys and a move trigger
+const LEVEL: &str = "kS38,1_40_2_125_3_255_11_255_12_255_13_255_4_-1_6_1000_7_1_15_1_18_0_8_1|,kA13,0,kA15,0,kA16,0,kA14,,kA6,0,kA7,0,kA17,0,kA18,0,kS39,0,kA2,0,kA3,0,kA8,0,kA4,0,kA9,0,kA10,0,kA11,0;\
+1,31,2,315,3,105,155,1,kA4,2,kA2,0,kA3,0,kA8,0,kA21,0,kA28,0,kA29,0,kA31,1,kA32,1,kA33,0,kA34,1,kA35,0,kA36,0,kA37,1,kA38,0,kA39,0,kA40,0,kA41,0;\
+1,1,2,45,3,15,57,5.6,20,1,128,0.5,129,1.25,155,1,156,4;\
+1,901,2,75,3,2115,20,1,28,10,29,-20,51,5,10,0.1,108,3,36,1,393,1;";
+
+#[test]
+fn unknown_keys_survive_round_trip() {
+    let obj = Obj::try_from("1,1,2,45,3,15,155,1,156,4,kA4,2".to_string()).unwrap();
+    assert_eq!(obj.get_raw("155"), Some("1"));
+    assert_eq!(obj.get_raw("kA4"), Some("2"));
+    assert_eq!(obj.as_str(), "1,1,2,45,3,15,155,1,156,4,kA4,2");
+}
+
+#[test]
+fn level_round_trip() {
+    let level = Level::try_from(LEVEL.to_string()).unwrap();
+    assert_eq!(level.as_str(), LEVEL);
+}
+
+#[test]
+fn new_attributes_are_sorted() {
+    let obj = Obj::new(901, 15.0, 45.0)
+        .with(LinkedGroupID, Int(2))
+        .with(MoveOffsetX, Int(10))
+        .with(TargetGroupID, Int(5))
+        .with(EditorLayer1, Int(1));
+    assert_eq!(obj.as_str(), "1,901,2,15,3,45,20,1,28,10,51,5,108,2");
+}
+
+#[test]
+fn set_keeps_parsed_order() {
+    let mut obj = Obj::try_from("3,15,2,45,1,1,kA4,2".to_string()).unwrap();
+    obj.set(XPos, Float(75.0));
+    obj.set(EditorLayer1, Int(3));
+    obj.set_raw("kA4", "1".to_string());
+    assert_eq!(obj.as_str(), "3,15,2,75,1,1,20,3,kA4,1");
+}
+
+#[test]
+fn malformed_values_are_reported() {
+    let result = Level::try_from("kA4,0;1,1,2,15,3,15;1,1,2,abc,3,15;".to_string());
+    match result {
+        Err(GdError::ObjectParse { index, key, value }) => {
+            assert_eq!((index, key, value.as_str()), (1, 2, "abc"));
+        },
+        _ => panic!("expected an object parse error")
+    }
+}
+
+#[test]
+fn non_canonical_values_survive_round_trip() {
+    let str = "1,1,2,15.0,3, 15 ,13,2,57,5.06,kA4,2,99";
+    let obj = Obj::try_from(str.to_string()).unwrap();
+    assert!(matches!(obj.get(XPos), Some(Float(x)) if *x == 15.0));
+    assert!(matches!(obj.get(YPos), Some(Float(y)) if *y == 15.0));
+    assert_eq!(obj.as_str(), str);
+    let trailing_comma = "1,1,2,45,3,15,";
+    assert_eq!(Obj::try_from(trailing_comma.to_string()).unwrap().as_str(), trailing_comma);
+
+    // Only the attributes that are set are rewritten, and new ones go before a trailing key
+    let mut obj = Obj::try_from(str.to_string()).unwrap();
+    obj.set(YPos, Float(30.0));
+    obj.set(EditorLayer1, Int(2));
+    assert_eq!(obj.as_str(), "1,1,2,15.0,3,30,13,2,20,2,57,5.06,kA4,2,99");
+}
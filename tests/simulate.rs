@@ -0,0 +1,334 @@
+use gdphysics::{LayerConfig, config::builtin_materials, Level, Obj, generated, object::{AttribKey::*, AttribValue::*}, physics::{self, SimulationReport}, speed::SpeedTracker};
+use std::path::PathBuf;
+
+fn data_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name)
+}
+
+/// Reads a level from `tests/data`
+fn load(name: &str) -> Level {
+    Level::try_from(std::fs::read_to_string(data_path(name)).unwrap()).unwrap()
+}
+
+/// Runs the simulation in `config` on the level, timed from the level's start speed
+fn run(level: &mut Level, config: &str) -> SimulationReport {
+    let tracker = SpeedTracker::new(&level.objects, level.header.speed());
+    let config: LayerConfig = serde_json::from_str(config).unwrap();
+    physics::simulate(&mut level.objects, config, &tracker, &builtin_materials())
+}
+
+/// Simulates `input` and compares the level string with `expected`.
+/// Run with `GDPHYSICS_UPDATE_GOLDEN=1` to rewrite the expected output
+fn golden(input: &str, expected: &str, config: &str) {
+    let mut level = load(input);
+    run(&mut level, config);
+    let output = level.as_str();
+
+    if std::env::var("GDPHYSICS_UPDATE_GOLDEN").is_ok() {
+        std::fs::write(data_path(expected), &output).unwrap();
+    }
+    let expected = std::fs::read_to_string(data_path(expected)).unwrap();
+    assert!(output == expected, "simulation output differs from {expected}");
+}
+
+#[test]
+fn bodies_golden() {
+    golden("bodies.txt", "bodies.expected.txt", r#"{
+        "layer": 1,
+        "sim_time": 2.0,
+        "objects": [
+            { "group": 5, "velocity": [100.0, 200.0] },
+            { "group": 7, "angular_velocity": 90.0, "restitution": 0.5 }
+        ]
+    }"#);
+}
+
+#[test]
+fn rerun_replaces_previous_triggers() {
+    let mut level = load("bodies.expected.txt");
+    run(&mut level, r#"{ "layer": 1, "sim_time": 2.0 }"#);
+    let triggers: Vec<&Obj> = level.objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(901)) | Some(Int(1346))))
+        .collect();
+    assert!(!triggers.is_empty());
+    assert!(triggers.iter().all(|o| matches!(o.get(LinkedGroupID), Some(Int(2)))));
+}
+
+const SPAWN: &str = r#"{
+    "layer": 1,
+    "sim_time": 2.0,
+    "output_mode": "spawn",
+    "objects": [
+        { "group": 5, "velocity": [100.0, 200.0] },
+        { "group": 7, "angular_velocity": 90.0, "restitution": 0.5 }
+    ]
+}"#;
+
+#[test]
+fn spawn_golden() {
+    golden("bodies.txt", "bodies.spawn.expected.txt", SPAWN);
+}
+
+fn groups(obj: &Obj) -> Vec<i32> {
+    match obj.get(GroupIDs) {
+        Some(Array(groups)) => groups.clone(),
+        _ => Vec::new()
+    }
+}
+
+#[test]
+fn spawn_chain_times_keyframes() {
+    let mut level = load("bodies.txt");
+    let tracker = SpeedTracker::new(&level.objects, level.header.speed());
+    // Running twice must replace the first chain
+    for _ in 0..2 {
+        run(&mut level, SPAWN);
+    }
+    let spawns: Vec<&Obj> = level.objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(1268))))
+        .collect();
+    let starts: Vec<&&Obj> = spawns.iter()
+        .filter(|o| !matches!(o.get(SpawnTriggered), Some(Bool(true))))
+        .collect();
+    assert_eq!(starts.len(), 1);
+    assert_eq!(physics::trigger_xs(&level.objects, 1), vec![300.0]);
+
+    // Follow the chain, recording when each group is spawned
+    let mut spawned_at = Vec::new();
+    let mut link = *starts[0];
+    let mut time = 0.0;
+    while let Some(Int(target)) = link.get(TargetGroupID) {
+        let Some(Float(delay)) = link.get(SpawnDelay) else { panic!("spawn trigger without a delay") };
+        time += delay;
+        assert!(*target > 8, "group {target} is already used by the level");
+        spawned_at.push((*target, time));
+        match spawns.iter().find(|o| groups(o).contains(target)) {
+            Some(next) => link = next,
+            None => break
+        }
+    }
+    assert!(spawned_at.len() > 10);
+
+    let start_time = tracker.time_at(300.0);
+    for trigger in level.objects.iter().filter(|o| matches!(o.get(ObjID), Some(Int(901)) | Some(Int(1346)))) {
+        assert!(matches!(trigger.get(SpawnTriggered), Some(Bool(true))));
+        let group = groups(trigger)[0];
+        let (_, time) = spawned_at.iter().find(|(g, _)| *g == group).expect("trigger group isn't spawned");
+        let expected = tracker.time_at(trigger.get_pos().0) - start_time;
+        assert!((time - expected).abs() < 1e-3, "group {group} spawns at {time}s, expected {expected}s");
+    }
+}
+
+fn start_triggers(level: &Level) -> Vec<&Obj> {
+    level.objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(1268))))
+        .filter(|o| !matches!(o.get(SpawnTriggered), Some(Bool(true))))
+        .collect()
+}
+
+#[test]
+fn platformer_starts_by_touch() {
+    let mut level = load("bodies.txt");
+    let config = SPAWN.replace("\"spawn\"", "\"platformer\"");
+    run(&mut level, &config);
+    let starts = start_triggers(&level);
+    assert_eq!(starts.len(), 1);
+    // The anchor is at (300, 15)
+    assert_eq!(starts[0].get_pos(), (300.0, 15.0));
+    assert!(matches!(starts[0].get(TouchTriggered), Some(Bool(true))));
+    assert!(physics::trigger_xs(&level.objects, 1).is_empty());
+
+    let config = SPAWN.replace("\"spawn\"", "\"platformer\", \"start_trigger_pos\": [600, 45]");
+    run(&mut level, &config);
+    let starts = start_triggers(&level);
+    assert_eq!(starts.len(), 1);
+    assert_eq!(starts[0].get_pos(), (600.0, 45.0));
+}
+
+#[test]
+fn platformer_starts_from_group() {
+    let mut level = load("bodies.txt");
+    let config = SPAWN.replace("\"spawn\"", "\"platformer\", \"start_group\": 20");
+    run(&mut level, &config);
+    assert!(start_triggers(&level).is_empty());
+    let spawns: Vec<&Obj> = level.objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(1268))))
+        .collect();
+    let generated = generated::generated_group(&level.objects).unwrap();
+    let start: Vec<&&Obj> = spawns.iter().filter(|o| groups(o).contains(&20)).collect();
+    assert_eq!(start.len(), 1);
+    // Keyframe groups are allocated above the start group
+    let Some(Int(first)) = start[0].get(TargetGroupID) else { panic!("start trigger has no target") };
+    assert_eq!(*first, 21);
+    assert!(spawns.iter().all(|o| groups(o).iter().all(|g| *g >= 20 || *g == generated)));
+}
+
+#[test]
+fn report_summarizes_changes() {
+    let mut level = load("bodies.txt");
+    // Two points in group 9 can't make a body
+    level.objects.push(Obj::new(1764, 600.0, 300.0).with(EditorLayer1, Int(1)).with(GroupIDs, Array(vec![9])));
+    level.objects.push(Obj::new(1764, 660.0, 300.0).with(EditorLayer1, Int(1)).with(GroupIDs, Array(vec![9])));
+    let config = r#"{ "layer": 1, "sim_time": 2.0, "objects": [{ "group": 5, "velocity": [100.0, 200.0] }] }"#;
+    let report = run(&mut level, config);
+    assert_eq!(report.layer, 1);
+    assert_eq!(report.bodies, 2);
+    assert_eq!(report.removed, 0);
+    assert!(report.generated > 0);
+    let (min, max) = report.x_range.unwrap();
+    assert_eq!(min, 300.0);
+    assert!(max > min);
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].contains("Group 9"), "{}", report.warnings[0]);
+
+    let rerun = run(&mut level, config);
+    assert_eq!(rerun.removed, report.generated);
+    assert_eq!(rerun.generated, report.generated);
+}
+
+#[test]
+fn report_warns_without_anchor() {
+    let mut level = load("bodies.txt");
+    let config = r#"{ "layer": 1, "anchor_id": 12345, "sim_time": 0.05 }"#;
+    let report = run(&mut level, config);
+    assert_eq!(report.generated, 0);
+    assert_eq!(report.x_range, None);
+    assert!(report.warnings.iter().any(|w| w.contains("No anchor")));
+}
+
+/// The move and rotate triggers in a level
+fn motion(level: &Level) -> Vec<String> {
+    level.objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(901)) | Some(Int(1346))))
+        .map(Obj::as_str)
+        .collect()
+}
+
+fn text(group: i32, text: &str) -> Obj {
+    let mut object = Obj::new(914, 330.0, 330.0).with(EditorLayer1, Int(1)).with(GroupIDs, Array(vec![group]));
+    object.set_text(text);
+    object
+}
+
+#[test]
+fn text_objects_configure_bodies() {
+    let expected = load("bodies.expected.txt");
+
+    let mut level = load("bodies.txt");
+    level.objects.push(text(5, "velocity=100,200"));
+    level.objects.push(text(7, "angular_velocity=90\nrestitution=0.5; density=1"));
+    let config = r#"{ "layer": 1, "sim_time": 2.0, "text_config": true }"#;
+    let report = run(&mut level, config);
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    // The text objects aren't part of the shapes, so the motion matches the JSON config
+    assert_eq!(motion(&level), motion(&expected));
+}
+
+#[test]
+fn text_objects_override_config() {
+    let mut level = load("bodies.txt");
+    level.objects.push(text(5, "material=platform"));
+    let config = r#"{ "layer": 1, "sim_time": 2.0, "text_config": true, "objects": [{ "group": 5, "velocity": [100.0, 200.0] }] }"#;
+    run(&mut level, config);
+    // Platforms don't move
+    assert!(motion(&level).iter().all(|t| !t.contains(",51,5,")));
+}
+
+#[test]
+fn invalid_text_objects_warn() {
+    let mut level = load("bodies.txt");
+    level.objects.push(text(5, "velocty=100,200 density=heavy"));
+    level.objects.push(text(7, "material=rubbr"));
+    let config = r#"{ "layer": 1, "sim_time": 2.0, "text_config": true }"#;
+    let report = run(&mut level, config);
+    assert_eq!(report.warnings, [
+        "Group 5 has a text object with an invalid parameter, text.density: expected float, found \"heavy\"",
+        "Group 5 has a text object with an invalid parameter, text.velocty: unknown field \"velocty\", did you mean \"velocity\"?",
+        "Group 7 has a text object with an invalid parameter, text.material: unknown material \"rubbr\", did you mean \"rubber\"?",
+    ]);
+}
+
+#[test]
+fn selected_groups_leave_the_rest_alone() {
+    let mut level = load("bodies.txt");
+    let before: Vec<String> = level.objects.iter().filter(|o| groups(o).contains(&7)).map(Obj::as_str).collect();
+    let config = r#"{ "layer": 1, "sim_time": 2.0, "groups": [5], "trigger_layer": 4, "objects": [{ "group": 5, "velocity": [100.0, 200.0] }] }"#;
+    let report = run(&mut level, config);
+    assert_eq!(report.bodies, 1);
+    let triggers = motion(&level);
+    assert!(!triggers.is_empty());
+    assert!(level.objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(901)) | Some(Int(1346))))
+        .all(|o| matches!(o.get(TargetGroupID), Some(Int(5))) && matches!(o.get(EditorLayer1), Some(Int(4)))));
+    let after: Vec<String> = level.objects.iter().filter(|o| groups(o).contains(&7)).map(Obj::as_str).collect();
+    assert_eq!(before, after);
+
+    // A range selects the same way, and a rerun only replaces the triggers on its trigger layer
+    let config = r#"{ "layer": 1, "sim_time": 2.0, "group_range": [6, 8] }"#;
+    let report = run(&mut level, config);
+    assert_eq!((report.bodies, report.removed), (1, 0));
+    assert_eq!(motion(&level).iter().filter(|t| triggers.contains(t)).count(), triggers.len());
+}
+
+#[test]
+fn generated_triggers_are_not_bodies() {
+    let mut level = load("bodies.txt");
+    // The anchor for the second simulation
+    level.objects.push(Obj::new(41, 300.0, 15.0).with(EditorLayer1, Int(2)));
+    let first = r#"{ "layer": 1, "sim_time": 2.0, "trigger_layer": 2, "objects": [{ "group": 5, "velocity": [100.0, 200.0] }] }"#;
+    let report = run(&mut level, first);
+    assert!(report.generated > 0);
+    let second = r#"{ "layer": 2, "sim_time": 2.0, "trigger_layer": 3 }"#;
+    let report = run(&mut level, second);
+    assert_eq!((report.bodies, report.generated), (0, 0));
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+}
+
+#[test]
+fn trigger_xs_ignores_hand_made_triggers() {
+    let mut level = load("bodies.txt");
+    level.objects.push(Obj::new(901, 1200.0, 500.0).with(EditorLayer1, Int(1)));
+    assert!(physics::trigger_xs(&level.objects, 1).is_empty());
+    run(&mut level, SPAWN);
+    assert_eq!(physics::trigger_xs(&level.objects, 1), vec![300.0]);
+}
+
+#[test]
+fn finds_selected_bodies() {
+    let mut level = load("bodies.txt");
+    level.objects.push(text(7, "density=2"));
+    let config: LayerConfig = serde_json::from_str(r#"{ "layer": 1, "text_config": true }"#).unwrap();
+    let bodies = physics::find_bodies(&level.objects, &config);
+    assert_eq!(bodies.keys().copied().collect::<Vec<i32>>(), [5, 7]);
+    assert_eq!((bodies[&5].points.len(), bodies[&5].center, bodies[&5].texts.len()), (5, Some(5), 0));
+    assert_eq!((bodies[&7].points.len(), bodies[&7].texts.len()), (4, 1));
+    assert!(bodies[&7].center.is_some());
+
+    let config: LayerConfig = serde_json::from_str(r#"{ "layer": 1, "groups": [7] }"#).unwrap();
+    let bodies = physics::find_bodies(&level.objects, &config);
+    // Without text_config the text object is one of the points
+    assert_eq!(bodies.keys().copied().collect::<Vec<i32>>(), [7]);
+    assert_eq!(bodies[&7].points.len(), 5);
+}
+
+#[test]
+fn reruns_reuse_keyframe_groups() {
+    let mut level = load("bodies.txt");
+    level.objects.push(Obj::new(41, 300.0, 15.0).with(EditorLayer1, Int(3)));
+    let configs = [
+        r#"{ "layer": 1, "sim_time": 2.0, "output_mode": "spawn", "groups": [5], "objects": [{ "group": 5, "velocity": [100.0, 200.0] }] }"#,
+        r#"{ "layer": 1, "sim_time": 2.0, "output_mode": "spawn", "groups": [7], "trigger_layer": 3, "objects": [{ "group": 7, "angular_velocity": 90.0 }] }"#
+    ];
+    let mut allocated = Vec::new();
+    for _ in 0..4 {
+        for config in configs {
+            run(&mut level, config);
+        }
+        let mut groups: Vec<i32> = level.objects.iter().flat_map(groups).collect();
+        groups.sort();
+        groups.dedup();
+        allocated.push(groups);
+    }
+    assert!(allocated.len() == 4 && allocated.iter().all(|groups| *groups == allocated[0]), "{allocated:?}");
+    assert_eq!(generated::max_group(&level.objects), *allocated[0].last().unwrap());
+}
@@ -0,0 +1,200 @@
+use gdphysics::{LayerConfig, config::builtin_materials, Level, Obj, object::{AttribKey::*, AttribValue::*}, physics, speed::{Speed, SpeedTracker, StartPos, StartPosCheck}};
+
+const SPEEDS: [Speed; 5] = [Speed::Half, Speed::Normal, Speed::Double, Speed::Triple, Speed::Quad];
+
+/// The bodies from `tests/data/bodies.txt`, starting at `start_speed`
+fn level(start_speed: Speed) -> Level {
+    let mut level = Level::try_from(include_str!("data/bodies.txt").to_string()).unwrap();
+    level.header.set_speed(start_speed);
+    level
+}
+
+fn trigger_xs(objects: &[Obj]) -> Vec<f32> {
+    objects.iter()
+        .filter(|o| matches!(o.get(ObjID), Some(Int(901))))
+        .map(|o| o.get_pos().0)
+        .collect()
+}
+
+fn simulate(level: &mut Level, config: &str) -> Vec<f32> {
+    let config: LayerConfig = serde_json::from_str(config).unwrap();
+    let start_speed = config.start_speed.unwrap_or_else(|| level.header.speed());
+    let tracker = SpeedTracker::new(&level.objects, start_speed);
+    physics::simulate(&mut level.objects, config, &tracker, &builtin_materials());
+    trigger_xs(&level.objects)
+}
+
+fn assert_spacing(xs: &[f32], speed: Speed) {
+    // 6 frames at 60 fps between keyframes
+    let spacing = speed.bps() * 30.0 * 0.1;
+    assert!(xs.len() > 10);
+    for (i, x) in xs.iter().enumerate() {
+        let expected = 300.0 + spacing * i as f32;
+        assert!((x - expected).abs() < 0.01, "{speed:?}: trigger {i} at {x}, expected {expected}");
+    }
+}
+
+const MOVING: &str = r#"{ "layer": 1, "ground": false, "gravity": [0, 0], "sim_time": 2.0, "objects": [{ "group": 5, "velocity": [300, 0], "linear_damping": 0 }] }"#;
+
+#[test]
+fn tracker_starts_at_start_speed() {
+    for speed in SPEEDS {
+        let tracker = SpeedTracker::new(&Vec::new(), speed);
+        assert_eq!(tracker.speed_at(0.0), speed);
+        assert_eq!(tracker.speed_at(1000.0), speed);
+    }
+}
+
+#[test]
+fn triggers_follow_header_speed() {
+    for speed in SPEEDS {
+        let mut level = level(speed);
+        assert_eq!(level.header.speed(), speed);
+        assert_spacing(&simulate(&mut level, MOVING), speed);
+    }
+}
+
+#[test]
+fn config_overrides_header_speed() {
+    for speed in SPEEDS {
+        let mut level = level(Speed::Normal);
+        let config = MOVING.replace(r#""layer": 1,"#, &format!(r#""layer": 1, "start_speed": "{}","#, format!("{speed:?}").to_lowercase()));
+        assert_spacing(&simulate(&mut level, &config), speed);
+    }
+}
+
+/// A checked portal of the given speed whose left edge is at `x`
+fn portal(speed: Speed, x: f32) -> Obj {
+    let id = match speed {
+        Speed::Half => 200,
+        Speed::Normal => 201,
+        Speed::Double => 202,
+        Speed::Triple => 203,
+        Speed::Quad => 1334
+    };
+    Obj::new(id, x + speed.width() / 2.0, 15.0).with(SpecialCheck, Bool(true))
+}
+
+#[test]
+fn time_and_x_integrate_across_portals() {
+    // Portals out of order in the object list must still be sorted by x
+    let objects = vec![portal(Speed::Quad, 900.0), portal(Speed::Double, 600.0)];
+    let tracker = SpeedTracker::new(&objects, Speed::Normal);
+    let t600 = 600.0 / Speed::Normal.units_per_second();
+    let t900 = t600 + 300.0 / Speed::Double.units_per_second();
+    assert!((tracker.time_at(600.0) - t600).abs() < 1e-5);
+    assert!((tracker.time_at(900.0) - t900).abs() < 1e-5);
+    assert!((tracker.time_at(1200.0) - (t900 + 300.0 / Speed::Quad.units_per_second())).abs() < 1e-5);
+    assert!((tracker.x_at(t900) - 900.0).abs() < 0.01);
+    for x in [-50.0, 0.0, 150.0, 599.0, 600.0, 750.0, 901.0, 5000.0] {
+        let roundtrip = tracker.x_at(tracker.time_at(x));
+        assert!((roundtrip - x).abs() < 0.01, "x {x} came back as {roundtrip}");
+    }
+}
+
+#[test]
+fn triggers_keep_time_across_portals() {
+    let mut level = level(Speed::Normal);
+    level.objects.push(portal(Speed::Triple, 500.0));
+    let tracker = SpeedTracker::new(&level.objects, Speed::Normal);
+    let xs = simulate(&mut level, MOVING);
+    assert!(xs.iter().any(|x| *x > 500.0));
+    let start = tracker.time_at(300.0);
+    for (i, x) in xs.iter().enumerate() {
+        let time = tracker.time_at(*x) - start;
+        assert!((time - 0.1 * i as f32).abs() < 1e-4, "trigger {i} fires at {time}s");
+    }
+}
+
+#[test]
+fn unchecked_portals_change_speed() {
+    let unchecked = portal(Speed::Double, 600.0).with(SpecialCheck, Bool(false));
+    let tracker = SpeedTracker::new(&vec![unchecked], Speed::Normal);
+    assert_eq!(tracker.speed_at(599.0), Speed::Normal);
+    assert_eq!(tracker.speed_at(600.0), Speed::Double);
+    assert!(tracker.warnings.is_empty());
+}
+
+#[test]
+fn portals_are_sorted_by_x() {
+    let objects = vec![portal(Speed::Quad, 900.0), portal(Speed::Half, 300.0), portal(Speed::Double, 600.0)];
+    let tracker = SpeedTracker::new(&objects, Speed::Normal);
+    assert_eq!(tracker.speeds, vec![(Speed::Normal, 0.0), (Speed::Half, 300.0), (Speed::Double, 600.0), (Speed::Quad, 900.0)]);
+}
+
+#[test]
+fn overlapping_portals_warn() {
+    let objects = vec![portal(Speed::Double, 600.0), portal(Speed::Triple, 600.0), portal(Speed::Quad, 900.0), portal(Speed::Quad, 900.0)];
+    let tracker = SpeedTracker::new(&objects, Speed::Normal);
+    // The last portal in the level wins
+    assert_eq!(tracker.speed_at(600.0), Speed::Triple);
+    assert_eq!(tracker.speeds.len(), 3);
+    // Identical portals aren't ambiguous
+    assert_eq!(tracker.warnings.len(), 1);
+    assert!(tracker.warnings[0].contains("x = 600"), "{}", tracker.warnings[0]);
+}
+
+fn start_pos(x: f32, speed: Speed) -> Obj {
+    let mut obj = Obj::new(31, x, 15.0);
+    obj.set_raw("kA4", speed.header_id().to_string());
+    obj
+}
+
+#[test]
+fn finds_start_positions() {
+    let objects = vec![start_pos(900.0, Speed::Quad), portal(Speed::Double, 300.0), start_pos(150.0, Speed::Half)];
+    let positions = StartPos::find(&objects);
+    assert_eq!(positions.len(), 2);
+    assert_eq!((positions[0].index, positions[0].x, positions[0].speed), (2, 150.0, Speed::Half));
+    assert_eq!((positions[1].index, positions[1].x, positions[1].speed), (0, 900.0, Speed::Quad));
+}
+
+#[test]
+fn tracker_from_start_pos_ignores_earlier_portals() {
+    let objects = vec![portal(Speed::Double, 300.0), portal(Speed::Triple, 900.0), start_pos(600.0, Speed::Half)];
+    let start = &StartPos::find(&objects)[0];
+    let tracker = SpeedTracker::from_start_pos(&objects, start);
+    assert_eq!(tracker.speeds, vec![(Speed::Half, 600.0), (Speed::Triple, 900.0)]);
+    assert_eq!(tracker.time_at(600.0), 0.0);
+    assert!((tracker.time_at(900.0) - 300.0 / Speed::Half.units_per_second()).abs() < 1e-5);
+}
+
+#[test]
+fn checks_start_positions() {
+    let mut level = level(Speed::Normal);
+    level.objects.push(portal(Speed::Double, 400.0));
+    level.objects.push(start_pos(100.0, Speed::Normal));
+    level.objects.push(start_pos(200.0, Speed::Half));
+    level.objects.push(start_pos(350.0, Speed::Normal));
+    level.objects.push(start_pos(450.0, Speed::Double));
+    let tracker = SpeedTracker::new(&level.objects, Speed::Normal);
+    simulate(&mut level, MOVING);
+    let xs = physics::trigger_xs(&level.objects, 1);
+    let checks: Vec<StartPosCheck> = StartPos::find(&level.objects).iter()
+        .map(|s| tracker.check_start_pos(&level.objects, s, &xs))
+        .collect();
+    assert_eq!(checks[0], StartPosCheck::Ok);
+    assert!(matches!(checks[1], StartPosCheck::Desync(e) if e > 0.05));
+    assert_eq!(checks[2], StartPosCheck::AfterMotion(300.0));
+    assert_eq!(checks[3], StartPosCheck::AfterMotion(300.0));
+}
+
+#[test]
+fn triggers_timed_from_start_pos() {
+    let mut level = level(Speed::Normal);
+    level.objects.push(start_pos(200.0, Speed::Quad));
+    let config = MOVING.replace(r#""layer": 1,"#, r#""layer": 1, "start_pos": 0,"#);
+    let config: LayerConfig = serde_json::from_str(&config).unwrap();
+    let tracker = SpeedTracker::for_layer(&level.objects, level.header.speed(), &config).unwrap();
+    physics::simulate(&mut level.objects, config, &tracker, &builtin_materials());
+    // The triggers follow the start position's speed rather than the level's
+    assert_spacing(&trigger_xs(&level.objects), Speed::Quad);
+    let xs = physics::trigger_xs(&level.objects, 1);
+    let start = &StartPos::find(&level.objects)[0];
+    assert_eq!(tracker.check_start_pos(&level.objects, start, &xs), StartPosCheck::Ok);
+    let from_level = SpeedTracker::new(&level.objects, level.header.speed());
+    assert!(matches!(from_level.check_start_pos(&level.objects, start, &xs), StartPosCheck::Desync(_)));
+
+    let missing: LayerConfig = serde_json::from_str(r#"{ "layer": 1, "start_pos": 1 }"#).unwrap();
+    assert!(SpeedTracker::for_layer(&level.objects, level.header.speed(), &missing).is_err());
+}